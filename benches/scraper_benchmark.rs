@@ -1,9 +1,9 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use html_parser::{HtmlScraper, ScrapeConfig, ScrapeRule, ScraperConfig};
+use html_parser::{HtmlScraper, HtmlScraperBuilder, ScrapeConfig, ScrapeRule, ScraperConfig};
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::borrow::Cow;
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct Article {
     title: String,
@@ -13,27 +13,11 @@ struct Article {
 
 impl ScrapeConfig for Article {
     fn get_config() -> ScraperConfig {
-        ScraperConfig::new(vec![
-                ScrapeRule::One {
-                    selector: "h1".to_string(),
-                    name: "title".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
-                ScrapeRule::One {
-                    selector: ".author".to_string(),
-                    name: "author".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
-                ScrapeRule::All {
-                    selector: "p".to_string(),
-                    name: "content".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
-            ]
-        )
+        ScraperConfig::builder()
+            .rule(ScrapeRule::one("h1", "title"))
+            .rule(ScrapeRule::one(".author", "author"))
+            .rule(ScrapeRule::all("p", "content"))
+            .build()
     }
 }
 
@@ -46,6 +30,46 @@ impl From<HashMap<String, String>> for Article {
    }
 }
 
+// The same fields as `Article`, deserialized with serde rather than `From`
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct ArticleFields {
+    title: String,
+    author: String,
+    content: Vec<String>,
+}
+
+impl ScrapeConfig for ArticleFields {
+    fn get_config() -> ScraperConfig {
+        Article::get_config()
+    }
+}
+
+// The heading and paragraphs of every section
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct Sections {
+    sections: Vec<String>,
+}
+
+impl ScrapeConfig for Sections {
+    fn get_config() -> ScraperConfig {
+        serde_json::from_str(r#"{
+            "rules": [
+                {
+                    "type": "All",
+                    "selector": "section",
+                    "name": "sections",
+                    "sub_rules": [
+                        { "type": "One", "selector": "h2", "name": "heading" },
+                        { "type": "All", "selector": "p", "name": "paragraphs" }
+                    ]
+                }
+            ]
+        }"#).unwrap()
+    }
+}
+
 fn generate_sample_html(paragraphs: usize) -> String {
     let mut html = String::from(r#"
         <html>
@@ -63,6 +87,19 @@ fn generate_sample_html(paragraphs: usize) -> String {
     html
 }
 
+fn generate_sectioned_html(sections: usize, paragraphs: usize) -> String {
+    let mut html = String::from("<html><body>");
+    for i in 0..sections {
+        html.push_str(&format!("<section><h2>Section {}</h2>", i));
+        for j in 0..paragraphs {
+            html.push_str(&format!("<p>This is paragraph {} of section {}.</p>", j, i));
+        }
+        html.push_str("</section>");
+    }
+    html.push_str("</body></html>");
+    html
+}
+
 fn bench_scrape(c: &mut Criterion) {
     let html = generate_sample_html(100);  // 100 paragraphs
     let scraper = HtmlScraper::default();
@@ -87,5 +124,69 @@ fn bench_scrape(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_scrape);
+fn bench_deserialize(c: &mut Criterion) {
+    let html = generate_sample_html(1000);
+    let scraper = HtmlScraper::default();
+
+    let mut group = c.benchmark_group("deserialize");
+    group.bench_function("scrape_deserialize", |b| {
+        b.iter(|| {
+            let _article: ArticleFields = scraper.scrape_deserialize(black_box(&html)).unwrap();
+        })
+    });
+    group.bench_function("scrape_value + from_value", |b| {
+        b.iter(|| {
+            let value = scraper.scrape_value::<ArticleFields>(black_box(&html)).unwrap();
+            let _article: ArticleFields = serde_json::from_value(value).unwrap();
+        })
+    });
+    group.finish();
+}
+
+fn bench_sections(c: &mut Criterion) {
+    let serial = HtmlScraper::default();
+    #[cfg(feature = "parallel")]
+    let parallel = HtmlScraperBuilder::new().parallel_sections(true).build();
+
+    // Few large sections, where a thread per section should pay off, and
+    // many small ones, where reparsing each one dominates
+    let mut group = c.benchmark_group("sections");
+    for &(sections, paragraphs) in &[(8, 2000), (2000, 8)] {
+        let html = generate_sectioned_html(sections, paragraphs);
+        group.bench_function(format!("serial {}x{}", sections, paragraphs), |b| {
+            b.iter(|| {
+                let _sections: Sections = serial.scrape_deserialize(black_box(&html)).unwrap();
+            })
+        });
+        #[cfg(feature = "parallel")]
+        group.bench_function(format!("parallel {}x{}", sections, paragraphs), |b| {
+            b.iter(|| {
+                let _sections: Sections = parallel.scrape_deserialize(black_box(&html)).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_selector_cache(c: &mut Criterion) {
+    let html = generate_sample_html(10);
+    let precompiled = HtmlScraperBuilder::new().try_build::<Article>().unwrap();
+
+    // A scraper per page compiles every selector on each scrape, a shared
+    // one only once
+    let mut group = c.benchmark_group("selector_cache");
+    group.bench_function("scraper per page", |b| {
+        b.iter(|| {
+            let _article: Article = HtmlScraperBuilder::new().build().scrape(black_box(&html)).unwrap();
+        })
+    });
+    group.bench_function("precompiled scraper", |b| {
+        b.iter(|| {
+            let _article: Article = precompiled.scrape(black_box(&html)).unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_scrape, bench_deserialize, bench_sections, bench_selector_cache);
 criterion_main!(benches);
\ No newline at end of file
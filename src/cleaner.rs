@@ -1,6 +1,15 @@
+use std::sync::Arc;
+
 // New trait for text cleaning
 pub trait TextCleaner: Send + Sync {
     fn clean(&self, text: &str) -> String;
+
+    /// The regexes the cleaner matches with, which
+    /// `HtmlScraperBuilder::try_build` checks against the builder's limits
+    #[cfg(feature = "regex")]
+    fn regexes(&self) -> Vec<&regex::Regex> {
+        Vec::new()
+    }
 }
 
 // Default text cleaner that removes newlines and extra whitespace
@@ -15,3 +24,305 @@ impl TextCleaner for DefaultCleaner {
             .join(" ")
     }
 }
+
+// Lowercases text, e.g. for case-insensitive matching downstream
+pub struct LowercaseCleaner;
+
+impl TextCleaner for LowercaseCleaner {
+    fn clean(&self, text: &str) -> String {
+        text.to_lowercase()
+    }
+}
+
+// Uppercases text
+pub struct UppercaseCleaner;
+
+impl TextCleaner for UppercaseCleaner {
+    fn clean(&self, text: &str) -> String {
+        text.to_uppercase()
+    }
+}
+
+// Named entities `EntityDecodeCleaner` decodes
+const NAMED_ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{a0}'),
+    ("shy", '\u{ad}'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("times", '×'),
+    ("divide", '÷'),
+    ("sect", '§'),
+    ("para", '¶'),
+    ("middot", '·'),
+    ("bull", '•'),
+    ("hellip", '…'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("sbquo", '‚'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("bdquo", '„'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("cent", '¢'),
+    ("pound", '£'),
+    ("yen", '¥'),
+    ("euro", '€'),
+];
+
+// Decodes HTML entities left in text by double-encoded markup, e.g. a
+// literal "&amp;#8217;" that parses to "&#8217;". Handles common named
+// entities and decimal and hex character references; anything else, such
+// as "&foo;" or a reference without its ";", is kept as is.
+pub struct EntityDecodeCleaner;
+
+impl EntityDecodeCleaner {
+    fn decode(entity: &str) -> Option<char> {
+        if let Some(number) = entity.strip_prefix('#') {
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            return char::from_u32(code).filter(|&c| c != '\0');
+        }
+        NAMED_ENTITIES
+            .iter()
+            .find(|(name, _)| *name == entity)
+            .map(|&(_, c)| c)
+    }
+}
+
+impl TextCleaner for EntityDecodeCleaner {
+    fn clean(&self, text: &str) -> String {
+        let mut decoded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('&') {
+            decoded.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let entity = rest[1..]
+                .find(';')
+                .map(|end| &rest[1..end + 1])
+                .filter(|entity| entity.len() <= 32);
+            match entity.and_then(|entity| Some((entity, EntityDecodeCleaner::decode(entity)?))) {
+                Some((entity, c)) => {
+                    decoded.push(c);
+                    rest = &rest[entity.len() + 2..];
+                }
+                None => {
+                    decoded.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        decoded.push_str(rest);
+        decoded
+    }
+}
+
+// Normalizes a localized number like "€ 1.234,56 kg" to "1234.56"
+pub struct NumberCleaner {
+    thousands_separator: char,
+    decimal_separator: char,
+}
+
+impl NumberCleaner {
+    pub fn new(thousands_separator: char, decimal_separator: char) -> Self {
+        NumberCleaner {
+            thousands_separator,
+            decimal_separator,
+        }
+    }
+
+    /// "1,234.56"
+    pub fn us() -> Self {
+        NumberCleaner::new(',', '.')
+    }
+
+    /// "1.234,56"
+    pub fn eu() -> Self {
+        NumberCleaner::new('.', ',')
+    }
+}
+
+impl Default for NumberCleaner {
+    fn default() -> Self {
+        NumberCleaner::us()
+    }
+}
+
+impl TextCleaner for NumberCleaner {
+    // Keeps the first number in the text, dropping currency symbols, units
+    // and grouping. Returns an empty string if the text has no digits.
+    fn clean(&self, text: &str) -> String {
+        let mut number = String::new();
+        // Characters since a minus sign before the number; the sign only
+        // counts if at most a currency symbol or a space separates it from
+        // the digits, so "Sale - €12,99" isn't negative
+        let mut since_sign = None;
+        let mut seen_decimal = false;
+
+        for c in text.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+            } else if number.is_empty() {
+                since_sign = match since_sign {
+                    _ if c == '-' || c == '\u{2212}' => Some(0),
+                    Some(0) if c.is_whitespace() || is_currency_symbol(c) => Some(1),
+                    _ => None,
+                };
+            } else if c == self.decimal_separator && !seen_decimal {
+                seen_decimal = true;
+                number.push('.');
+            } else if c == self.thousands_separator || c == ' ' || c == '\u{a0}' || c == '\u{202f}' {
+                continue;
+            } else {
+                break;
+            }
+        }
+
+        let number = number.trim_end_matches('.');
+        if number.is_empty() {
+            String::new()
+        } else if since_sign.is_some() {
+            format!("-{}", number)
+        } else {
+            number.to_string()
+        }
+    }
+}
+
+fn is_currency_symbol(c: char) -> bool {
+    matches!(c, '$' | '€' | '£' | '¥' | '¢' | '₹' | '₩' | '₽' | '₺' | '₪' | '₫' | '฿' | '₴' | '₦')
+}
+
+// Cleans like `DefaultCleaner`, then strips a leading list marker such as
+// "• " or "1. " that sites copy into the text of list items
+pub struct ListMarkerCleaner {
+    markers: Vec<String>,
+    numbered: bool,
+}
+
+impl ListMarkerCleaner {
+    /// Strips any of `markers` when followed by whitespace
+    pub fn new(markers: &[&str]) -> Self {
+        ListMarkerCleaner {
+            markers: markers.iter().map(|m| m.to_string()).collect(),
+            numbered: false,
+        }
+    }
+
+    /// Also strip numbering like "1." or "12)"
+    pub fn with_numbers(mut self, numbered: bool) -> Self {
+        self.numbered = numbered;
+        self
+    }
+
+    fn strip_number<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let digits = text.find(|c: char| !c.is_ascii_digit())?;
+        if digits == 0 {
+            return None;
+        }
+        text[digits..]
+            .strip_prefix(['.', ')'])
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+    }
+}
+
+impl Default for ListMarkerCleaner {
+    fn default() -> Self {
+        ListMarkerCleaner::new(&["•", "◦", "▪", "‣", "·", "–", "-", "*"]).with_numbers(true)
+    }
+}
+
+impl TextCleaner for ListMarkerCleaner {
+    fn clean(&self, text: &str) -> String {
+        let text = DefaultCleaner.clean(text);
+        let stripped = self
+            .markers
+            .iter()
+            .filter_map(|marker| text.strip_prefix(marker.as_str()))
+            .find(|rest| rest.starts_with(char::is_whitespace))
+            .or_else(|| self.numbered.then(|| self.strip_number(&text)).flatten());
+        match stripped {
+            Some(rest) => rest.trim_start().to_string(),
+            None => text,
+        }
+    }
+}
+
+// Applies several cleaners in order, each to the output of the one before
+#[derive(Default)]
+pub struct ChainCleaner {
+    cleaners: Vec<Arc<dyn TextCleaner>>,
+}
+
+impl ChainCleaner {
+    pub fn new(cleaners: Vec<Arc<dyn TextCleaner>>) -> Self {
+        ChainCleaner { cleaners }
+    }
+
+    /// Appends a cleaner, run after the ones already in the chain
+    pub fn push(&mut self, cleaner: Arc<dyn TextCleaner>) {
+        self.cleaners.push(cleaner);
+    }
+}
+
+impl TextCleaner for ChainCleaner {
+    fn clean(&self, text: &str) -> String {
+        self.cleaners
+            .iter()
+            .fold(text.to_string(), |text, cleaner| cleaner.clean(&text))
+    }
+
+    #[cfg(feature = "regex")]
+    fn regexes(&self) -> Vec<&regex::Regex> {
+        self.cleaners.iter().flat_map(|cleaner| cleaner.regexes()).collect()
+    }
+}
+
+// Replaces every match of a regex, e.g. `\[\d+\]` footnote markers or a
+// "Published: " prefix. `$1`-style references in the replacement expand to
+// capture groups.
+#[cfg(feature = "regex")]
+pub struct RegexReplaceCleaner {
+    regex: regex::Regex,
+    replacement: String,
+}
+
+#[cfg(feature = "regex")]
+impl RegexReplaceCleaner {
+    /// Compiles `pattern` within the `regex` crate's default limits
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, regex::Error> {
+        Ok(RegexReplaceCleaner::from_regex(regex::Regex::new(pattern)?, replacement))
+    }
+
+    /// Replaces matches of a regex compiled elsewhere, e.g. with
+    /// `RegexLimits::compile` for other limits
+    pub fn from_regex(regex: regex::Regex, replacement: &str) -> Self {
+        RegexReplaceCleaner {
+            regex,
+            replacement: replacement.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+impl TextCleaner for RegexReplaceCleaner {
+    fn clean(&self, text: &str) -> String {
+        self.regex.replace_all(text, self.replacement.as_str()).into_owned()
+    }
+
+    fn regexes(&self) -> Vec<&regex::Regex> {
+        vec![&self.regex]
+    }
+}
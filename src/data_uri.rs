@@ -0,0 +1,93 @@
+// `data:` URI parsing (RFC 2397) and the base64 it relies on
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | ((b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Decodes standard base64, ignoring whitespace and with optional padding
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let digits = encoded
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    let digits = digits.strip_suffix(b"==").or_else(|| digits.strip_suffix(b"=")).unwrap_or(&digits);
+
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, &digit) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|&c| c == digit)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// The media type and payload of a `data:` URI, `None` if `uri` isn't one
+/// or its base64 is malformed
+pub(crate) fn parse(uri: &str) -> Option<(String, Vec<u8>)> {
+    let uri = uri.trim();
+    if !uri.get(..5)?.eq_ignore_ascii_case("data:") {
+        return None;
+    }
+    let (header, payload) = uri[5..].split_once(',')?;
+
+    let (mime, is_base64) = match header.len().checked_sub(7) {
+        Some(i) if header.as_bytes()[i..].eq_ignore_ascii_case(b";base64") => (&header[..i], true),
+        _ => (header, false),
+    };
+    let mime = match mime.trim() {
+        "" => "text/plain;charset=US-ASCII",
+        mime => mime,
+    };
+
+    let payload = percent_decode(payload);
+    let bytes = if is_base64 {
+        base64_decode(std::str::from_utf8(&payload).ok()?)?
+    } else {
+        payload
+    };
+    Some((mime.to_string(), bytes))
+}
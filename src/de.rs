@@ -0,0 +1,153 @@
+// Deserializes scraped fields directly, without first building a
+// `serde_json::Value` of the whole result
+
+use std::collections::HashMap;
+
+use serde::de::{
+    self,
+    value::{MapDeserializer, SeqDeserializer, StringDeserializer},
+    DeserializeOwned, IntoDeserializer, Visitor,
+};
+use serde_json::{Map, Value};
+
+type Error = serde_json::Error;
+
+// A scraped field value. Deserializes as a string, as a number or bool by
+// parsing it, and as a sequence, map or struct by parsing the JSON that
+// `All`, `sub_rules` and the like store.
+struct Field(String);
+
+impl Field {
+    // Elements of stored JSON are fields in turn, as `All` with `sub_rules`
+    // stores an array of JSON-encoded objects
+    fn from_value(value: Value) -> Self {
+        match value {
+            Value::String(s) => Field(s),
+            value => Field(value.to_string()),
+        }
+    }
+
+    fn visit_json_seq<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let items: Vec<Value> = serde_json::from_str(&self.0)?;
+        let mut seq = SeqDeserializer::new(items.into_iter().map(Field::from_value));
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn visit_json_map<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let entries: Map<String, Value> = serde_json::from_str(&self.0)?;
+        let mut map = MapDeserializer::new(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, Field::from_value(value))),
+        );
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let value = self.0.trim().parse().map_err(de::Error::custom)?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Field {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant: StringDeserializer<Error> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.visit_json_seq(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.visit_json_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.visit_json_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.visit_json_map(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.visit_json_map(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Field {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserializes `T` from the fields scraped for it, keyed by rule name
+pub(crate) fn from_fields<T: DeserializeOwned>(fields: HashMap<String, String>) -> Result<T, Error> {
+    T::deserialize(MapDeserializer::new(
+        fields.into_iter().map(|(name, value)| (name, Field(value))),
+    ))
+}
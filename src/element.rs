@@ -0,0 +1,31 @@
+use scraper::ElementRef;
+
+use crate::{cleaner::TextCleaner, visitor::ScraperVisitor};
+
+/// The crate's text helpers, for working with `scraper` elements directly
+pub trait ElementExt<'a> {
+    /// The text directly inside the element, without that of child elements
+    fn own_text(&self) -> String;
+
+    /// The text of the element and its descendants, leaving out nested
+    /// `<script>`, `<style>` and `<template>` elements, passed through
+    /// `cleaner`
+    fn cleaned_text(&self, cleaner: &dyn TextCleaner) -> String;
+
+    /// The value of attribute `name`, or `default` if the element lacks it
+    fn attr_or(&self, name: &str, default: &'a str) -> &'a str;
+}
+
+impl<'a> ElementExt<'a> for ElementRef<'a> {
+    fn own_text(&self) -> String {
+        ScraperVisitor::default().own_text(self, None).unwrap_or_default()
+    }
+
+    fn cleaned_text(&self, cleaner: &dyn TextCleaner) -> String {
+        cleaner.clean(&ScraperVisitor::default().collect_text(self))
+    }
+
+    fn attr_or(&self, name: &str, default: &'a str) -> &'a str {
+        self.value().attr(name).unwrap_or(default)
+    }
+}
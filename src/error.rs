@@ -13,4 +13,59 @@ pub enum ConfigError {
     UnsupportedFormat,
     #[error("TOML support is not enabled. Enable the 'toml_config' feature to use TOML configs.")]
     TomlNotEnabled,
-}
\ No newline at end of file
+    #[error("Invalid selector: {0}")]
+    InvalidSelector(String),
+    /// The builder's base URL isn't an absolute URL, see
+    /// `HtmlScraperBuilder::with_base_url`
+    #[error("Invalid base URL: {0}")]
+    InvalidBaseUrl(String),
+    /// A regex exceeds the `RegexLimits` it is compiled or checked with
+    #[cfg(feature = "regex")]
+    #[error("Regex exceeds the compiled size limit of {0} bytes")]
+    RegexTooComplex(usize),
+}
+
+/// An error scraping a page with a config, as opposed to loading the config
+#[derive(Error, Debug)]
+pub enum ScrapeError {
+    /// The config couldn't be loaded or one of its selectors is invalid
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[cfg(feature = "regex")]
+    #[error("Regex error: {0}")]
+    Regex(regex::Error),
+    #[cfg(feature = "jsonpath")]
+    #[error("Invalid JSONPath: {0}")]
+    InvalidJsonPath(String),
+    #[error("Invalid base URL: {0}")]
+    InvalidBaseUrl(String),
+    #[error("Required field '{0}' matched nothing")]
+    RequiredFieldMissing(String),
+    #[error("Value '{value}' of '{name}' can't be coerced to the rule's type")]
+    CoercionFailed { name: String, value: String },
+    #[error("Value '{value}' of '{name}' failed validation")]
+    ValidationFailed { name: String, value: String },
+    #[error("Relative URL '{value}' for '{name}' and no base URL to resolve it against")]
+    RelativeUrl { name: String, value: String },
+    #[error("Invalid data URI for '{0}'")]
+    InvalidDataUri(String),
+    #[error("More than {0} elements matched by All rules")]
+    MatchLimitExceeded(usize),
+    #[error("No cleaner registered as '{0}'")]
+    UnknownCleaner(String),
+    /// A validator names no rule whose field it could check, e.g. a typo or
+    /// a sub-rule of an `All`, whose fields are nested in its items
+    #[error("No rule '{0}' with a field to validate")]
+    UnknownValidator(String),
+    /// Rules that matched nothing in strict mode, see `HtmlScraperBuilder::strict`
+    #[error("Rules matched nothing or came out empty: {}", .0.join(", "))]
+    Unmatched(Vec<String>),
+    /// The scraped fields don't fit the target type
+    #[error("Deserialization error: {0}")]
+    Deserialize(serde_json::Error),
+    /// Fetching the page failed, or the server answered with a non-2xx status
+    #[cfg(feature = "http")]
+    #[error("HTTP error: {0}")]
+    Http(reqwest::Error),
+}
+
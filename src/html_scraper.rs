@@ -1,16 +1,110 @@
-use std::{collections::HashMap, fmt::{self, Debug, Formatter}, sync::Arc};
+use std::{collections::HashMap, fmt::{self, Debug, Formatter}, iter, sync::Arc};
 
-use scraper::Html;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::Serialize;
 
-use crate::{cleaner::TextCleaner, scraper_config::ScrapeConfig, visitor::{ScraperVisitor, Visitor}, ConfigError};
+use crate::{cleaner::TextCleaner, de, scraper_config::{ScrapeConfig, ScrapeRule, ScraperConfig}, url, table::Table, visitor::{ordered_fields, select, structured_fields, ElementFilter, FieldResult, ScraperVisitor, SelectorCache, Visitor}, xml, ConfigError, ScrapeError};
 
 
+/// The boolean attributes defined by the HTML spec
+pub const HTML_BOOLEAN_ATTRIBUTES: &[&str] = &[
+    "allowfullscreen",
+    "async",
+    "autofocus",
+    "autoplay",
+    "checked",
+    "controls",
+    "default",
+    "defer",
+    "disabled",
+    "formnovalidate",
+    "hidden",
+    "inert",
+    "ismap",
+    "itemscope",
+    "loop",
+    "multiple",
+    "muted",
+    "nomodule",
+    "novalidate",
+    "open",
+    "playsinline",
+    "readonly",
+    "required",
+    "reversed",
+    "selected",
+];
+
+/// A comment of a thread flattened by `HtmlScraper::scrape_threaded`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CommentRecord {
+    pub id: String,
+    /// The id of the comment this one is nested in, `None` at the top level
+    pub parent_id: Option<String>,
+    /// How many comments this one is nested in
+    pub depth: usize,
+    pub content: String,
+}
+
+/// How the rules of a config fare on a sample page, see
+/// `HtmlScraper::validate`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ValidationReport {
+    /// One entry per top-level rule, in config order
+    pub rules: Vec<RuleReport>,
+}
+
+impl ValidationReport {
+    /// Whether every selector compiled and every required rule matched
+    pub fn is_ok(&self) -> bool {
+        self.rules.iter().all(|rule| rule.compiled && !rule.missing_required)
+    }
+
+    /// The rules that matched nothing on the page
+    pub fn unmatched(&self) -> impl Iterator<Item = &RuleReport> {
+        self.rules.iter().filter(|rule| rule.matches == 0)
+    }
+}
+
+/// A rule's entry in a `ValidationReport`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RuleReport {
+    pub name: String,
+    /// Whether all of the rule's selectors, including nested rules', compile
+    pub compiled: bool,
+    /// How many elements the rule's selector matches in the whole page. For
+    /// `Coalesce`, `Resolve` and `OneOf`, those of the first alternative
+    /// that matches.
+    pub matches: usize,
+    /// Whether the rule is `required` and would fail the scrape
+    pub missing_required: bool,
+}
+
 /// A builder for the `HtmlScraper` struct
 /// That allows for configuring the scraper
 /// before building it
+#[derive(Default)]
 pub struct HtmlScraperBuilder {
     config: Option<String>,
     cleaner: Option<Arc<dyn TextCleaner>>,
+    cleaners: HashMap<String, Arc<dyn TextCleaner>>,
+    base_url: Option<String>,
+    keep_script_style: bool,
+    normalize_attribute_names: bool,
+    boolean_attributes: Vec<String>,
+    max_total_matches: Option<usize>,
+    raw_text: bool,
+    parse_xml: bool,
+    preserve_preformatted: bool,
+    hidden_classes: Vec<String>,
+    element_filter: Option<ElementFilter>,
+    #[cfg(feature = "parallel")]
+    parallel_sections: bool,
+    strict: bool,
+    #[cfg(feature = "http")]
+    user_agent: Option<String>,
+    #[cfg(feature = "regex")]
+    regex_limits: RegexLimits,
 }
 
 impl HtmlScraperBuilder {
@@ -18,6 +112,24 @@ impl HtmlScraperBuilder {
         HtmlScraperBuilder {
             config: None,
             cleaner: None,
+            cleaners: HashMap::new(),
+            base_url: None,
+            keep_script_style: false,
+            normalize_attribute_names: false,
+            boolean_attributes: Vec::new(),
+            max_total_matches: None,
+            raw_text: false,
+            parse_xml: false,
+            preserve_preformatted: false,
+            hidden_classes: Vec::new(),
+            element_filter: None,
+            #[cfg(feature = "parallel")]
+            parallel_sections: false,
+            strict: false,
+            #[cfg(feature = "http")]
+            user_agent: None,
+            #[cfg(feature = "regex")]
+            regex_limits: RegexLimits::default(),
         }
     }
 
@@ -31,14 +143,287 @@ impl HtmlScraperBuilder {
         self
     }
 
+    /// Like `with_cleaner`, but shares an existing cleaner instead of
+    /// wrapping a new one, so many scrapers can use the same instance
+    pub fn with_cleaner_arc(mut self, cleaner: Arc<dyn TextCleaner>) -> Self {
+        self.cleaner = Some(cleaner);
+        self
+    }
+
+    /// Like `with_cleaner`, for a cleaner only chosen at runtime, e.g. by
+    /// name from a config file
+    pub fn with_boxed_cleaner(mut self, cleaner: Box<dyn TextCleaner>) -> Self {
+        self.cleaner = Some(Arc::from(cleaner));
+        self
+    }
+
+    /// Makes `cleaner` available to rules that name it in their `cleaner`
+    /// field. Those rules use it instead of the one set with `with_cleaner`.
+    pub fn register_cleaner<T: TextCleaner + 'static>(mut self, name: &str, cleaner: T) -> Self {
+        self.cleaners.insert(name.to_string(), Arc::new(cleaner));
+        self
+    }
+
+    /// The URL relative links are resolved against, usually the address of the page.
+    /// It must be absolute, which `try_build` checks.
+    pub fn with_base_url(mut self, url: &str) -> Self {
+        self.base_url = Some(url.to_string());
+        self
+    }
+
+    /// Whether text inside `<script>`, `<style>` and `<template>` elements
+    /// is left out when collecting an element's text. Defaults to `true`.
+    pub fn skip_script_style(mut self, skip: bool) -> Self {
+        self.keep_script_style = !skip;
+        self
+    }
+
+    /// Match the attribute names of rules case-insensitively, so a config
+    /// asking for `data-ID` finds `data-id`. The HTML parser already
+    /// lowercases the names in the markup.
+    pub fn normalize_attribute_names(mut self, normalize: bool) -> Self {
+        self.normalize_attribute_names = normalize;
+        self
+    }
+
+    /// Attributes that are true when present, whatever their value. Attribute
+    /// rules for these yield `"true"` or `"false"` instead of the value, so
+    /// `checked=""` and `checked="checked"` both read as checked. See
+    /// `HTML_BOOLEAN_ATTRIBUTES` for the ones defined by the HTML spec.
+    pub fn with_boolean_attributes(mut self, attributes: &[&str]) -> Self {
+        self.boolean_attributes = attributes.iter().map(|a| a.to_string()).collect();
+        self
+    }
+
+    /// Caps the number of elements materialized by all `All` rules of a
+    /// scrape together, failing with `ScrapeError::MatchLimitExceeded` once
+    /// crossed. Guards memory on adversarial pages.
+    pub fn with_max_total_matches(mut self, limit: usize) -> Self {
+        self.max_total_matches = Some(limit);
+        self
+    }
+
+    /// Keep entities escaped in extracted text, for consumers that render
+    /// it back into HTML.
+    ///
+    /// The parser decodes entities and doesn't keep the source text, so this
+    /// can't reproduce the markup byte for byte. Instead text is escaped the
+    /// way `inner_html` serializes it: `&`, `<`, `>` and no-break spaces
+    /// become `&amp;`, `&lt;`, `&gt;` and `&nbsp;`, while other characters,
+    /// e.g. one written as `&#8217;` in the source, come out decoded.
+    pub fn raw_text(mut self, raw: bool) -> Self {
+        self.raw_text = raw;
+        self
+    }
+
+    /// Treat documents as XML served as HTML, e.g. RSS or Atom feeds.
+    ///
+    /// Documents are still parsed by the HTML5 parser, which handles custom
+    /// tags like `<item>` but not XML itself. This option rewrites the three
+    /// constructs it would mangle before parsing: CDATA sections become text,
+    /// self-closing tags like `<enclosure/>` get an end tag, and elements
+    /// named like HTML void elements get an `x-` prefix to keep their
+    /// content. RSS `<link>` is then selected as `x-link`. Tag names are
+    /// case-insensitive as in HTML, so `pubDate` and `pubdate` both match.
+    pub fn parse_xml(mut self, xml: bool) -> Self {
+        self.parse_xml = xml;
+        self
+    }
+
+    /// Exempt text inside `<pre>` and `<code>` elements from the cleaner, so
+    /// code keeps its indentation and line breaks while the text around it
+    /// is cleaned as usual. Preformatted blocks are set off from the
+    /// surrounding text by line breaks and inline code by spaces.
+    pub fn preserve_preformatted(mut self, preserve: bool) -> Self {
+        self.preserve_preformatted = preserve;
+        self
+    }
+
+    /// Treat elements with any of these classes, e.g. `sr-only` or
+    /// `visually-hidden`, as absent: rules don't match them or anything
+    /// inside them, and their text is left out of their ancestors' text
+    pub fn with_hidden_classes(mut self, classes: Vec<String>) -> Self {
+        self.hidden_classes = classes;
+        self
+    }
+
+    /// Skip the matches of rules for which `filter` returns `false`, as if
+    /// they didn't match, e.g. a `Count` leaves them out and a `One` takes
+    /// the first match the filter accepts.
+    pub fn with_element_filter(mut self, filter: ElementFilter) -> Self {
+        self.element_filter = Some(filter);
+        self
+    }
+
+    /// Evaluate top-level `All` rules with `sub_rules` on the rayon thread
+    /// pool, a thread per matched section.
+    ///
+    /// Parsed documents can't be shared between threads, so each section is
+    /// serialized and parsed again on its own, which costs about as much as
+    /// parsing it did the first time. That pays off for a few large sections
+    /// with many or expensive sub-rules and is slower for many small ones;
+    /// measure with the `sections` benchmark. Sub-rule selectors only see
+    /// the section itself, so ones relying on its ancestors match
+    /// differently, and `with_section` in sub-rules only finds headings
+    /// inside it. Sections that can't stand alone as a fragment, like
+    /// table rows, and scrapes using tags, provenance or a match limit are
+    /// evaluated serially.
+    #[cfg(feature = "parallel")]
+    pub fn parallel_sections(mut self, parallel: bool) -> Self {
+        self.parallel_sections = parallel;
+        self
+    }
+
+    /// Fail scrapes with `ScrapeError::Unmatched`, listing every top-level
+    /// rule that matched nothing or whose value came out empty after
+    /// cleaning, e.g. to notice in production that a site changed its
+    /// layout. A `default` doesn't make up for a rule matching nothing.
+    /// `Optional` rules and rules skipped for their tags are exempt.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// The `User-Agent` header `scrape_url` sends, instead of reqwest's default
+    #[cfg(feature = "http")]
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Upper bound in bytes on the size of a compiled regex, see
+    /// `regex::RegexBuilder::size_limit`
+    #[cfg(feature = "regex")]
+    pub fn with_regex_size_limit(mut self, bytes: usize) -> Self {
+        self.regex_limits.size_limit = Some(bytes);
+        self
+    }
+
+    /// Upper bound in bytes on the lazy DFA cache of a regex, see
+    /// `regex::RegexBuilder::dfa_size_limit`
+    #[cfg(feature = "regex")]
+    pub fn with_regex_dfa_size_limit(mut self, bytes: usize) -> Self {
+        self.regex_limits.dfa_size_limit = Some(bytes);
+        self
+    }
+
+    /// Both regex limits at once, e.g. the ones regex cleaners and
+    /// validators are compiled with
+    #[cfg(feature = "regex")]
+    pub fn with_regex_limits(mut self, limits: RegexLimits) -> Self {
+        self.regex_limits = limits;
+        self
+    }
+
     pub fn build(self) -> HtmlScraper {
         HtmlScraper {
             config: self.config,
             cleaner: self.cleaner,
+            cleaners: self.cleaners,
+            base_url: self.base_url,
+            keep_script_style: self.keep_script_style,
+            normalize_attribute_names: self.normalize_attribute_names,
+            boolean_attributes: self.boolean_attributes,
+            max_total_matches: self.max_total_matches,
+            raw_text: self.raw_text,
+            parse_xml: self.parse_xml,
+            preserve_preformatted: self.preserve_preformatted,
+            hidden_classes: self.hidden_classes,
+            element_filter: self.element_filter,
+            selectors: SelectorCache::default(),
+            #[cfg(feature = "parallel")]
+            parallel_sections: self.parallel_sections,
+            strict: self.strict,
+            #[cfg(feature = "http")]
+            client: http_client(self.user_agent.as_deref()).ok(),
+            #[cfg(feature = "http")]
+            user_agent: self.user_agent,
+            #[cfg(feature = "regex")]
+            regex_limits: self.regex_limits,
+        }
+    }
+
+    /// Builds like `build`, but loads the config of `T` and compiles all of
+    /// its selectors up front, so an invalid one fails here with
+    /// `ConfigError::InvalidSelector` rather than on the first scrape, and
+    /// no scrape pays for compiling them.
+    ///
+    /// A base URL that isn't absolute fails with `ConfigError::InvalidBaseUrl`.
+    /// The regexes of the config's validators and of the cleaners are checked
+    /// against the builder's regex limits too, as they are compiled before
+    /// the limits are known. One exceeding them fails with
+    /// `ConfigError::RegexTooComplex`.
+    pub fn try_build<T: ScrapeConfig>(self) -> Result<HtmlScraper, ConfigError> {
+        if let Some(base) = self.base_url.as_deref().filter(|base| !url::is_absolute(base)) {
+            return Err(ConfigError::InvalidBaseUrl(base.to_string()));
+        }
+        let scraper = self.build();
+        let config = scraper.scraper_config::<T>()?;
+        config.compile(&scraper.selectors)?;
+        #[cfg(feature = "regex")]
+        {
+            let cleaners = scraper.cleaner.iter().chain(scraper.cleaners.values());
+            for regex in config.regexes().chain(cleaners.flat_map(|cleaner| cleaner.regexes())) {
+                scraper.regex_limits.check(regex)?;
+            }
         }
+        Ok(scraper)
     }
 }
 
+/// Upper bounds on compiling a regex, applied to every pattern a scraper,
+/// cleaner or validator compiles. `None` keeps the `regex` crate's default.
+///
+/// The `regex` crate never backtracks, so matching is always linear in the
+/// input and a pathological pattern can't hang a scrape. What it can do is
+/// compile into a very large program; the limits bound that.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegexLimits {
+    /// Bytes of the compiled regex, see `regex::RegexBuilder::size_limit`
+    pub size_limit: Option<usize>,
+    /// Bytes of the lazy DFA cache, see `regex::RegexBuilder::dfa_size_limit`
+    pub dfa_size_limit: Option<usize>,
+}
+
+#[cfg(feature = "regex")]
+impl RegexLimits {
+    /// Compiles `pattern`, failing with `ConfigError::RegexTooComplex` if it
+    /// exceeds the limits
+    pub fn compile(&self, pattern: &str) -> Result<regex::Regex, ScrapeError> {
+        let mut builder = regex::RegexBuilder::new(pattern);
+        if let Some(limit) = self.size_limit {
+            builder.size_limit(limit);
+        }
+        if let Some(limit) = self.dfa_size_limit {
+            builder.dfa_size_limit(limit);
+        }
+        builder.build().map_err(|e| match e {
+            regex::Error::CompiledTooBig(limit) => ConfigError::RegexTooComplex(limit).into(),
+            e => ScrapeError::Regex(e),
+        })
+    }
+
+    // Whether `regex`, compiled elsewhere, fits within the limits too
+    pub(crate) fn check(&self, regex: &regex::Regex) -> Result<(), ConfigError> {
+        if self.size_limit.is_none() && self.dfa_size_limit.is_none() {
+            return Ok(());
+        }
+        match self.compile(regex.as_str()) {
+            Err(ScrapeError::Config(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn http_client(user_agent: Option<&str>) -> reqwest::Result<reqwest::Client> {
+    let mut client = reqwest::Client::builder();
+    if let Some(user_agent) = user_agent {
+        client = client.user_agent(user_agent);
+    }
+    client.build()
+}
 
 /// A struct that can scrape HTML documents
 /// 
@@ -53,10 +438,33 @@ impl HtmlScraperBuilder {
 /// 
 /// 
 /// ```
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct HtmlScraper {
     config: Option<String>,
     cleaner: Option<Arc<dyn TextCleaner>>,
+    cleaners: HashMap<String, Arc<dyn TextCleaner>>,
+    base_url: Option<String>,
+    keep_script_style: bool,
+    normalize_attribute_names: bool,
+    boolean_attributes: Vec<String>,
+    max_total_matches: Option<usize>,
+    raw_text: bool,
+    parse_xml: bool,
+    preserve_preformatted: bool,
+    hidden_classes: Vec<String>,
+    element_filter: Option<ElementFilter>,
+    selectors: SelectorCache,
+    #[cfg(feature = "parallel")]
+    parallel_sections: bool,
+    strict: bool,
+    // Shared by every `scrape_url`, `None` if it couldn't be built, e.g. for
+    // an invalid user agent, which `scrape_url` then reports
+    #[cfg(feature = "http")]
+    client: Option<reqwest::Client>,
+    #[cfg(feature = "http")]
+    user_agent: Option<String>,
+    #[cfg(feature = "regex")]
+    regex_limits: RegexLimits,
 }
 
 impl Debug for HtmlScraper {
@@ -66,40 +474,1018 @@ impl Debug for HtmlScraper {
 }
 
 impl HtmlScraper {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> HtmlScraperBuilder {
         HtmlScraperBuilder::new()
     }
     pub fn scrape<T: ScrapeConfig + for<'a> From<HashMap<String, String>>>(
         &self,
         html: &str,
-    ) -> Result<T, ConfigError> {
-        let scraper_config = if let Some(config_str) = &self.config {
-            T::from_config(&config_str)?
+    ) -> Result<T, ScrapeError> {
+        self.scrape_document(&self.parse(html))
+    }
+
+    /// Fetches `url` and scrapes the page body like `scrape`. Failing to
+    /// connect and non-2xx responses are `ScrapeError::Http`.
+    #[cfg(feature = "http")]
+    pub async fn scrape_url<T: ScrapeConfig + for<'a> From<HashMap<String, String>>>(
+        &self,
+        url: &str,
+    ) -> Result<T, ScrapeError> {
+        let client = match &self.client {
+            Some(client) => client.clone(),
+            None => http_client(self.user_agent.as_deref()).map_err(ScrapeError::Http)?,
+        };
+        let html = client
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(ScrapeError::Http)?
+            .text()
+            .await
+            .map_err(ScrapeError::Http)?;
+        self.scrape(&html)
+    }
+
+    /// Like `scrape`, for a document that is already parsed, e.g. to run
+    /// several configs over a page while parsing it once.
+    ///
+    /// The document is only borrowed for the call, and the result owns its
+    /// strings, so the document can be reused or dropped right after.
+    /// Elements with one of the `with_hidden_classes` are skipped as usual,
+    /// leaving `document` untouched. `parse_xml` can't apply, as it rewrites
+    /// the markup before it is parsed.
+    pub fn scrape_document<T: ScrapeConfig + for<'a> From<HashMap<String, String>>>(
+        &self,
+        document: &Html,
+    ) -> Result<T, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+
+        let result = if self.has_hidden(document) {
+            let mut document = document.clone();
+            self.remove_hidden(&mut document);
+            self.visit_parsed(&mut visitor, &scraper_config, &document)?
+        } else {
+            self.visit_parsed(&mut visitor, &scraper_config, document)?
+        };
+
+        Ok(T::from(result))
+    }
+
+    /// Scrapes every document in `htmls` on the rayon thread pool, e.g. a
+    /// crawl cached on disk. Like `scrape_batch`, which it defers to, the
+    /// config is loaded once and there is a result per document.
+    #[cfg(feature = "parallel")]
+    pub fn scrape_many<T>(&self, htmls: &[String]) -> Vec<Result<T, ScrapeError>>
+    where
+        T: ScrapeConfig + From<HashMap<String, String>> + Send,
+    {
+        self.scrape_batch(htmls)
+    }
+
+    /// Scrapes like `scrape`, but rules carrying `tags` only run if one of
+    /// them is in `tags`, so one config can serve e.g. a "summary" and a
+    /// "full" extraction. Untagged rules always run; this applies to nested
+    /// rules as well.
+    pub fn scrape_tagged<T: ScrapeConfig + From<HashMap<String, String>>>(
+        &self,
+        html: &str,
+        tags: &[&str],
+    ) -> Result<T, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+        visitor.tags = Some(tags.iter().map(|tag| tag.to_string()).collect());
+        let result = self.visit_document(&mut visitor, &scraper_config, html)?;
+
+        Ok(T::from(result))
+    }
+
+    /// Scrapes every document with the same config, e.g. the pages listed in
+    /// a sitemap. A failing document doesn't stop the batch: there is a
+    /// result per document, in input order. With the `parallel` feature the
+    /// documents are scraped on the rayon thread pool.
+    pub fn scrape_batch<T>(&self, docs: impl IntoIterator<Item = impl AsRef<str> + Sync>) -> Vec<Result<T, ScrapeError>>
+    where
+        T: ScrapeConfig + From<HashMap<String, String>> + Send,
+    {
+        let scrape = |scraper_config: &ScraperConfig, html: &str| -> Result<T, ScrapeError> {
+            let mut visitor = self.visitor();
+            Ok(T::from(self.visit_document(&mut visitor, scraper_config, html)?))
+        };
+        let scraper_config = match self.scraper_config::<T>() {
+            Ok(config) => config,
+            // Every document fails; errors aren't `Clone`, so each one gets
+            // its own from loading the config again
+            Err(_) => {
+                return docs
+                    .into_iter()
+                    .map(|html| scrape(&self.scraper_config::<T>()?, html.as_ref()))
+                    .collect()
+            }
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            let docs: Vec<_> = docs.into_iter().collect();
+            docs.par_iter().map(|html| scrape(&scraper_config, html.as_ref())).collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            docs.into_iter().map(|html| scrape(&scraper_config, html.as_ref())).collect()
+        }
+    }
+
+    /// Scrapes into a JSON object, for callers that want to inspect the
+    /// result without a target type. Fields are in the order their rules are
+    /// declared, so serializing the result is stable. Values can be borrowed
+    /// from the returned `Value` with its accessors.
+    ///
+    /// Nested results are real arrays and objects rather than the JSON
+    /// strings `scrape` hands to `From<HashMap<String, String>>`, e.g. `All`
+    /// with `sub_rules` gives an array of objects. `Text` and other
+    /// single-value rules give strings.
+    ///
+    /// ```
+    /// use html_parser::{HtmlScraper, ScrapeConfig, ScrapeRule, ScraperConfig};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Page;
+    ///
+    /// impl ScrapeConfig for Page {
+    ///     fn get_config() -> ScraperConfig {
+    ///         ScraperConfig::new(vec![ScrapeRule::text("h1", "title")])
+    ///     }
+    /// }
+    ///
+    /// let value = HtmlScraper::default()
+    ///     .scrape_value::<Page>("<h1>Hello</h1>")
+    ///     .unwrap();
+    /// let title: &str = value["title"].as_str().unwrap();
+    /// assert_eq!(title, "Hello");
+    /// ```
+    pub fn scrape_value<T: ScrapeConfig>(&self, html: &str) -> Result<serde_json::Value, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+        let result = self.visit_document(&mut visitor, &scraper_config, html)?;
+
+        Ok(serde_json::Value::Object(structured_fields(&scraper_config.rules, result)))
+    }
+
+    /// Scrapes like `scrape_value` and returns the values matched by the
+    /// JSONPath expression `path`, e.g. `$..title`.
+    ///
+    /// Nested results of `All`, `sub_rules` and the like are queried as the
+    /// objects and arrays `scrape_value` returns. Supported are `.name`, `['name']`,
+    /// `[n]` with negative `n` counting from the end, `*` and `..`.
+    #[cfg(feature = "jsonpath")]
+    pub fn scrape_jsonpath<T: ScrapeConfig>(
+        &self,
+        html: &str,
+        path: &str,
+    ) -> Result<Vec<serde_json::Value>, ScrapeError> {
+        crate::jsonpath::query(&self.scrape_value::<T>(html)?, path)
+    }
+
+    /// Scrapes and deserializes the result into `T` with serde, instead of
+    /// going through a `From<HashMap<String, String>>` impl.
+    ///
+    /// Fields are deserialized directly, without an intermediate `Value`.
+    /// Besides strings, a field deserializes as a number or bool by parsing
+    /// it, and as a `Vec`, map or struct from the JSON that `All`,
+    /// `sub_rules` and the like store.
+    ///
+    /// The fields are owned by this call, so `T` can't borrow from them. `ScrapeConfig` requires `for<'de> Deserialize<'de>`
+    /// for that reason, which rules out `#[serde(borrow)]` fields:
+    ///
+    /// ```compile_fail
+    /// use html_parser::{ScrapeConfig, ScraperConfig};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Borrowed<'a> {
+    ///     #[serde(borrow)]
+    ///     title: &'a str,
+    /// }
+    ///
+    /// impl<'a> ScrapeConfig for Borrowed<'a> {
+    ///     fn get_config() -> ScraperConfig {
+    ///         ScraperConfig::new(vec![])
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Use `scrape_value` and borrow from the `Value` instead.
+    pub fn scrape_deserialize<T: ScrapeConfig>(&self, html: &str) -> Result<T, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+        let result = self.visit_document(&mut visitor, &scraper_config, html)?;
+
+        de::from_fields(result).map_err(ScrapeError::Deserialize)
+    }
+
+    /// Scrapes like `scrape`, but every field also records the selector
+    /// and the tag of the element it was extracted from
+    pub fn scrape_with_provenance<T: ScrapeConfig>(
+        &self,
+        html: &str,
+    ) -> Result<HashMap<String, FieldResult>, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+        visitor.sources = Some(HashMap::new());
+        let result = self.visit_document(&mut visitor, &scraper_config, html)?;
+
+        let mut sources = visitor.sources.unwrap_or_default();
+        Ok(result
+            .into_iter()
+            .map(|(name, value)| {
+                let mut field = sources.remove(&name).unwrap_or_default();
+                field.value = value;
+                (name, field)
+            })
+            .collect())
+    }
+
+    /// Applies the configured rules to every element matching `root_selector`
+    /// and yields one compact JSON object per element, e.g. to push as
+    /// server-sent event `data:` payloads. Records are extracted lazily as
+    /// the iterator is advanced.
+    pub fn scrape_stream<T: ScrapeConfig>(
+        &self,
+        html: &str,
+        root_selector: &str,
+    ) -> Result<impl Iterator<Item = Result<String, ScrapeError>>, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+        let cleaner = self.cleaner.clone();
+
+        let document = self.parse(html);
+        let selector = self.selectors.get(root_selector)?;
+        let mut roots = select(&document.root_element(), &selector)
+            .map(|root| root.id())
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        Ok(iter::from_fn(move || {
+            let root = ElementRef::wrap(document.tree.get(roots.next()?)?)?;
+            let mut record = HashMap::new();
+            for rule in &scraper_config.rules {
+                match visitor.visit_element(&root, rule, cleaner.as_deref()) {
+                    Ok(fields) => record.extend(fields),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            if let Err(e) = scraper_config.validate(&record) {
+                return Some(Err(e));
+            }
+            let record = ordered_fields(&scraper_config.rules, record);
+            Some(Ok(serde_json::to_string(&record).unwrap()))
+        }))
+    }
+
+    /// Checks the config against a representative page without extracting
+    /// anything, e.g. before deploying it or to notice that a site's layout
+    /// changed: whether each rule's selectors compile, how many elements
+    /// they match and whether a required rule would come up empty.
+    pub fn validate<T: ScrapeConfig>(&self, html: &str) -> Result<ValidationReport, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let document = self.parse(html);
+
+        let rules = scraper_config
+            .rules
+            .iter()
+            .map(|rule| {
+                let compiled = rule.selectors().iter().all(|selector| self.selectors.get(selector).is_ok());
+                let matches = rule
+                    .primary_selectors()
+                    .iter()
+                    .filter_map(|selector| self.selectors.get(selector).ok())
+                    .map(|selector| select(&document.root_element(), &selector).count())
+                    .find(|&count| count > 0)
+                    .unwrap_or(0);
+                RuleReport {
+                    name: rule.name().to_string(),
+                    compiled,
+                    matches,
+                    missing_required: rule.required() && matches == 0,
+                }
+            })
+            .collect();
+
+        Ok(ValidationReport { rules })
+    }
+
+    /// A structural fingerprint of the page against the config: how many
+    /// elements each configured selector matches in the whole document.
+    /// Comparing signatures across pages of a site points out layout changes.
+    pub fn signature<T: ScrapeConfig>(&self, html: &str) -> Result<HashMap<String, usize>, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let document = self.parse(html);
+        let mut signature = HashMap::new();
+
+        for selector in scraper_config.rules.iter().flat_map(|rule| rule.selectors()) {
+            let compiled = self.selectors.get(&selector)?;
+            signature.insert(selector.to_string(), select(&document.root_element(), &compiled).count());
+        }
+
+        Ok(signature)
+    }
+
+    /// A checksum of the scraped content, for deduplicating crawled pages.
+    ///
+    /// Only the configured fields are hashed, with whitespace collapsed, so
+    /// pages that differ in ads or other markup outside them still compare
+    /// equal. The hash is FNV-1a and stable across runs and builds, so it can
+    /// be stored alongside crawl results.
+    pub fn page_fingerprint<T: ScrapeConfig>(&self, html: &str) -> Result<u64, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+        let mut fields = self
+            .visit_document(&mut visitor, &scraper_config, html)?
+            .into_iter()
+            .collect::<Vec<_>>();
+        fields.sort();
+
+        let mut hash = FNV_OFFSET_BASIS;
+        for (name, value) in &fields {
+            hash = fnv1a(hash, name.as_bytes());
+            hash = fnv1a(hash, &[0]);
+            for (i, word) in value.split_whitespace().enumerate() {
+                if i > 0 {
+                    hash = fnv1a(hash, b" ");
+                }
+                hash = fnv1a(hash, word.as_bytes());
+            }
+            hash = fnv1a(hash, &[0]);
+        }
+
+        Ok(hash)
+    }
+
+    /// The fraction of configured fields that came out non-empty, from 0.0
+    /// to 1.0, to track scraper health across a site. Empty arrays and
+    /// objects, e.g. from an `All` rule that matched nothing, count as empty.
+    pub fn completeness<T: ScrapeConfig>(&self, html: &str) -> Result<f64, ScrapeError> {
+        let scraper_config = self.scraper_config::<T>()?;
+        let mut visitor = self.visitor();
+        let result = self.visit_document(&mut visitor, &scraper_config, html)?;
+
+        let names: Vec<&str> = scraper_config.rules.iter().flat_map(ScrapeRule::field_names).collect();
+        if names.is_empty() {
+            return Ok(1.0);
+        }
+        let present = names
+            .iter()
+            .filter(|name| {
+                result
+                    .get(**name)
+                    .is_some_and(|value| !matches!(value.trim(), "" | "[]" | "{}"))
+            })
+            .count();
+        Ok(present as f64 / names.len() as f64)
+    }
+
+    /// Every distinct `<a href>` on the page, in document order, for crawling.
+    ///
+    /// Links are resolved against `base_url`, or the builder's base URL if not
+    /// given. Empty hrefs and `javascript:`, `mailto:`, `tel:` and `data:`
+    /// links are skipped.
+    pub fn extract_links(&self, html: &str, base_url: Option<&str>) -> Result<Vec<String>, ScrapeError> {
+        let base_url = base_url.or(self.base_url.as_deref());
+        if let Some(base) = base_url {
+            if !url::is_absolute(base) {
+                return Err(ScrapeError::InvalidBaseUrl(base.to_string()));
+            }
+        }
+
+        let document = self.parse(html);
+        let selector = self.selectors.get("a[href]")?;
+        let mut links = Vec::new();
+
+        for anchor in select(&document.root_element(), &selector) {
+            let href = anchor.value().attr("href").unwrap_or("").trim();
+            if href.is_empty() {
+                continue;
+            }
+            if let Some("javascript" | "mailto" | "tel" | "data") = url::scheme(href).as_deref() {
+                continue;
+            }
+            let link = match base_url {
+                Some(base) => url::resolve(base, href).unwrap_or_else(|| href.to_string()),
+                None => href.to_string(),
+            };
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+
+        Ok(links)
+    }
+
+    /// Every distinct image URL on the page, in document order, including
+    /// lazy-loaded images.
+    ///
+    /// Each `<img>` contributes the first of `src`, `data-src`,
+    /// `data-lazy-src` and the first `srcset` candidate that is set and isn't
+    /// a `data:` URI, as lazy-loading scripts put placeholders in `src`. URLs
+    /// are resolved like in `extract_links`.
+    pub fn extract_images(&self, html: &str, base_url: Option<&str>) -> Result<Vec<String>, ScrapeError> {
+        let base_url = base_url.or(self.base_url.as_deref());
+        if let Some(base) = base_url {
+            if !url::is_absolute(base) {
+                return Err(ScrapeError::InvalidBaseUrl(base.to_string()));
+            }
+        }
+
+        let document = self.parse(html);
+        let selector = self.selectors.get("img")?;
+        let mut images = Vec::new();
+
+        for img in select(&document.root_element(), &selector) {
+            let img = img.value();
+            let srcset = img
+                .attr("srcset")
+                .and_then(|srcset| srcset.split(',').next())
+                .and_then(|candidate| candidate.split_whitespace().next());
+            let src = [img.attr("src"), img.attr("data-src"), img.attr("data-lazy-src"), srcset]
+                .into_iter()
+                .flatten()
+                .map(str::trim)
+                .find(|src| !src.is_empty() && url::scheme(src).as_deref() != Some("data"));
+            let Some(src) = src else {
+                continue;
+            };
+            let image = match base_url {
+                Some(base) => url::resolve(base, src).unwrap_or_else(|| src.to_string()),
+                None => src.to_string(),
+            };
+            if !images.contains(&image) {
+                images.push(image);
+            }
+        }
+
+        Ok(images)
+    }
+
+    /// Reads the first table matching `selector` into headers and rows.
+    ///
+    /// The first row is the header row if all its cells are `<th>`; without
+    /// one, `headers` is empty. A cell spanning several columns is repeated
+    /// for each of them, row spans aren't expanded. Rows of nested tables
+    /// aren't included. Cells are cleaned with the configured cleaner, or
+    /// else have their whitespace collapsed.
+    pub fn scrape_typed_table(&self, html: &str, selector: &str) -> Result<Option<Table>, ScrapeError> {
+        let selector = self.selectors.get(selector)?;
+        let document = self.parse(html);
+        let Some(table) = select(&document.root_element(), &selector).next() else {
+            return Ok(None);
+        };
+
+        let mut visitor = self.visitor();
+        let mut result = Table::default();
+        for (values, is_header) in visitor.table_rows(&table, self.cleaner.as_deref()) {
+            if is_header && result.headers.is_empty() && result.rows.is_empty() {
+                result.headers = values;
+            } else {
+                result.rows.push(values);
+            }
+        }
+
+        Ok(Some(result))
+    }
+
+    /// The crumbs of the first breadcrumb trail matching `selector`, e.g.
+    /// `nav[aria-label="breadcrumb"]`, in order, with their links.
+    ///
+    /// Crumbs are the `<li>`s of the trail, or its child elements if it has
+    /// none, and link to the first `href` inside them; the current page is
+    /// usually the last crumb and has no link. Crumbs without letters or
+    /// digits, like separators, are skipped. Text is cleaned like table
+    /// cells, and links are resolved against the builder's base URL, if set.
+    pub fn breadcrumbs(&self, html: &str, selector: &str) -> Result<Vec<(String, Option<String>)>, ScrapeError> {
+        let selector = self.selectors.get(selector)?;
+        let document = self.parse(html);
+        let Some(trail) = select(&document.root_element(), &selector).next() else {
+            return Ok(Vec::new());
+        };
+
+        let items = self.selectors.get("li")?;
+        let links = self.selectors.get("a[href]")?;
+        let mut crumbs: Vec<ElementRef> = trail.select(&items).collect();
+        if crumbs.is_empty() {
+            crumbs = trail.child_elements().collect();
+        }
+
+        let mut visitor = self.visitor();
+        let mut result = Vec::new();
+        for crumb in crumbs {
+            let text = visitor.collect_text(&crumb);
+            let text = match &self.cleaner {
+                Some(cleaner) => visitor.visit_text(&text, Some(cleaner.as_ref())),
+                None => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            };
+            if !text.chars().any(char::is_alphanumeric) {
+                continue;
+            }
+            let link = std::iter::once(crumb)
+                .filter(|crumb| links.matches(crumb))
+                .chain(crumb.select(&links))
+                .find_map(|link| link.value().attr("href"))
+                .map(str::trim)
+                .filter(|href| !href.is_empty())
+                .map(|href| match &self.base_url {
+                    Some(base) => url::resolve(base, href).unwrap_or_else(|| href.to_string()),
+                    None => href.to_string(),
+                });
+            result.push((text, link));
+        }
+
+        Ok(result)
+    }
+
+    /// Every comment matching `comment_selector`, in document order, with
+    /// the id of the closest comment it is nested in, turning a thread of
+    /// nested replies into a flat list.
+    ///
+    /// Ids are read from the `id_attr` attribute. The content is the text of
+    /// the first `content_selector` match that belongs to the comment rather
+    /// than to one of its replies, cleaned like table cells.
+    pub fn scrape_threaded(
+        &self,
+        html: &str,
+        comment_selector: &str,
+        id_attr: &str,
+        content_selector: &str,
+    ) -> Result<Vec<CommentRecord>, ScrapeError> {
+        let comments = self.selectors.get(comment_selector)?;
+        let content = self.selectors.get(content_selector)?;
+        let document = self.parse(html);
+
+        let mut visitor = self.visitor();
+        let mut records = Vec::new();
+        for comment in select(&document.root_element(), &comments) {
+            let id = |comment: ElementRef| comment.value().attr(id_attr).unwrap_or("").trim().to_string();
+            let ancestors = enclosing_matches(&comment, &comments);
+
+            let text = comment
+                .select(&content)
+                .find(|c| enclosing_matches(c, &comments).first().map(|p| p.id()) == Some(comment.id()))
+                .map(|c| visitor.collect_text(&c))
+                .unwrap_or_default();
+            let text = match &self.cleaner {
+                Some(cleaner) => visitor.visit_text(&text, Some(cleaner.as_ref())),
+                None => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            };
+
+            records.push(CommentRecord {
+                id: id(comment),
+                parent_id: ancestors.first().copied().map(id),
+                depth: ancestors.len(),
+                content: text,
+            });
+        }
+
+        Ok(records)
+    }
+
+    /// The document language from `<html lang>`, if declared
+    pub fn lang(&self, html: &str) -> Option<String> {
+        let document = self.parse(html);
+        let lang = document.root_element().value().attr("lang")?.trim();
+        (!lang.is_empty()).then(|| lang.to_string())
+    }
+
+    /// The `href`s of the page's `<link>` elements by relation, e.g.
+    /// `canonical`, `alternate` or `stylesheet`, in document order.
+    ///
+    /// A link with several space-separated `rel` tokens is listed under each,
+    /// lowercased since relations are case-insensitive. Hrefs are resolved
+    /// against the builder's base URL, if set.
+    pub fn link_relations(&self, html: &str) -> HashMap<String, Vec<String>> {
+        let document = self.parse(html);
+        let selector = self.selectors.get("link[rel][href]").expect("a valid selector");
+        let mut relations: HashMap<String, Vec<String>> = HashMap::new();
+
+        for link in select(&document.root_element(), &selector) {
+            let href = link.value().attr("href").unwrap_or("").trim();
+            if href.is_empty() {
+                continue;
+            }
+            let href = match &self.base_url {
+                Some(base) => url::resolve(base, href).unwrap_or_else(|| href.to_string()),
+                None => href.to_string(),
+            };
+            for rel in link.value().attr("rel").unwrap_or("").split_ascii_whitespace() {
+                let hrefs = relations.entry(rel.to_ascii_lowercase()).or_default();
+                if !hrefs.contains(&href) {
+                    hrefs.push(href.clone());
+                }
+            }
+        }
+
+        relations
+    }
+
+    /// The delay in seconds and target of a `<meta http-equiv="refresh">`
+    /// redirect, e.g. `content="5; url=/next"`. The target is resolved against
+    /// `base`, or else the builder's base URL. `None` without such a tag, or
+    /// when its `content` has no delay or no URL, as a bare delay only
+    /// reloads the page.
+    pub fn meta_refresh(&self, html: &str, base: Option<&str>) -> Option<(u32, String)> {
+        let document = self.parse(html);
+        let selector = self.selectors.get("meta[http-equiv][content]").expect("a valid selector");
+        let (delay, target) = select(&document.root_element(), &selector)
+            .filter(|meta| {
+                let http_equiv = meta.value().attr("http-equiv").unwrap_or("");
+                http_equiv.trim().eq_ignore_ascii_case("refresh")
+            })
+            .find_map(|meta| parse_refresh(meta.value().attr("content").unwrap_or("")))?;
+
+        let target = match base.or(self.base_url.as_deref()) {
+            Some(base) => url::resolve(base, target).unwrap_or_else(|| target.to_string()),
+            None => target.to_string(),
+        };
+        Some((delay, target))
+    }
+
+    /// The text of the page's main content, e.g. the article body, found
+    /// without any rules.
+    ///
+    /// Every paragraph scores its length for its parent element and half of
+    /// it for its grandparent. Scores are scaled down by the share of text in
+    /// links, which sinks navigation and link lists, and the text of the best
+    /// element is returned, cleaned with the configured cleaner or else with
+    /// whitespace collapsed. Falls back to the whole `<body>` for pages
+    /// without paragraphs.
+    pub fn main_content(&self, html: &str) -> Result<String, ScrapeError> {
+        let document = self.parse(html);
+        let visitor = self.visitor();
+        let paragraphs = self.selectors.get("p")?;
+        let links = self.selectors.get("a")?;
+
+        // Candidates in document order, so ties go to the first
+        let mut candidates = Vec::new();
+        let mut index = HashMap::new();
+        for paragraph in select(&document.root_element(), &paragraphs) {
+            let length = visitor.collect_text(&paragraph).trim().chars().count() as f64;
+            let parent = paragraph.parent().and_then(ElementRef::wrap);
+            let grandparent = parent.and_then(|parent| parent.parent()).and_then(ElementRef::wrap);
+            for (candidate, weight) in [(parent, 1.0), (grandparent, 0.5)] {
+                if let Some(candidate) = candidate {
+                    let i = *index.entry(candidate.id()).or_insert_with(|| {
+                        candidates.push((candidate, 0.0));
+                        candidates.len() - 1
+                    });
+                    candidates[i].1 += length * weight;
+                }
+            }
+        }
+
+        let mut best: Option<(ElementRef, f64)> = None;
+        for (candidate, score) in candidates {
+            let text_length = visitor.collect_text(&candidate).chars().count();
+            let link_length: usize = candidate
+                .select(&links)
+                .map(|link| visitor.collect_text(&link).chars().count())
+                .sum();
+            let link_density = link_length as f64 / text_length.max(1) as f64;
+            let score = score * (1.0 - link_density);
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((candidate, score));
+            }
+        }
+
+        let body = self.selectors.get("body")?;
+        let main = match best {
+            Some((element, _)) => Some(element),
+            None => select(&document.root_element(), &body).next(),
+        };
+        let text = main.map(|element| visitor.collect_text(&element)).unwrap_or_default();
+
+        Ok(match &self.cleaner {
+            Some(cleaner) => cleaner.clean(&text),
+            None => text.split_whitespace().collect::<Vec<_>>().join(" "),
+        })
+    }
+
+    /// The lead paragraph of the first element matching `content_selector`,
+    /// e.g. an article body, cut to `max_words` words.
+    ///
+    /// That is the first `<p>` with text at or below the element, cleaned with
+    /// the configured cleaner or else with whitespace collapsed. When words
+    /// are cut, `…` is appended in place of any trailing comma, colon or
+    /// semicolon. `None` if nothing matches or there is no such paragraph.
+    pub fn summary(&self, html: &str, content_selector: &str, max_words: usize) -> Result<Option<String>, ScrapeError> {
+        let content = self.selectors.get(content_selector)?;
+        let document = self.parse(html);
+        let Some(container) = select(&document.root_element(), &content).next() else {
+            return Ok(None);
+        };
+
+        let paragraphs = self.selectors.get("p")?;
+        let mut visitor = self.visitor();
+        let lead = iter::once(container)
+            .filter(|container| paragraphs.matches(container))
+            .chain(container.select(&paragraphs))
+            .map(|paragraph| {
+                let text = visitor.collect_text(&paragraph);
+                match &self.cleaner {
+                    Some(cleaner) => visitor.visit_text(&text, Some(cleaner.as_ref())),
+                    None => text.split_whitespace().collect::<Vec<_>>().join(" "),
+                }
+            })
+            .find(|text| !text.trim().is_empty());
+
+        Ok(lead.map(|lead| {
+            let words: Vec<&str> = lead.split_whitespace().collect();
+            if words.len() <= max_words {
+                return lead;
+            }
+            let cut = words[..max_words].join(" ");
+            format!("{}…", cut.trim_end_matches([',', ':', ';']))
+        }))
+    }
+
+    /// The `h1` to `h6` headings of the page as `(level, text)` in document
+    /// order, e.g. as an outline for summarization.
+    ///
+    /// Text is whitespace-collapsed and leaves out headings nested inside the
+    /// heading, which are listed on their own. Empty headings are skipped.
+    pub fn outline(&self, html: &str) -> Vec<(u8, String)> {
+        let document = self.parse(html);
+        let selector = self.selectors.get("h1, h2, h3, h4, h5, h6").expect("a valid selector");
+
+        select(&document.root_element(), &selector)
+            .filter_map(|heading| {
+                let level = heading.value().name()[1..].parse().ok()?;
+                let mut text = String::new();
+                heading_text(&heading, &mut text);
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                (!text.is_empty()).then_some((level, text))
+            })
+            .collect()
+    }
+
+    /// How many distinct selectors the scraper has compiled so far. Clones
+    /// share the compiled selectors, and each is compiled once, however many
+    /// pages it is used on. At most 1024 are kept, selectors past that are
+    /// compiled on each use.
+    pub fn compiled_selectors(&self) -> usize {
+        self.selectors.len()
+    }
+
+    /// Compiles a regex within the limits configured on the builder, see
+    /// `RegexLimits`. Exceeding them returns `ConfigError::RegexTooComplex`.
+    #[cfg(feature = "regex")]
+    pub fn regex(&self, pattern: &str) -> Result<regex::Regex, ScrapeError> {
+        self.regex_limits.compile(pattern)
+    }
+
+    fn scraper_config<T: ScrapeConfig>(&self) -> Result<ScraperConfig, ConfigError> {
+        if let Some(config_str) = &self.config {
+            T::from_config(config_str)
         } else {
-            T::get_config()
+            Ok(T::get_config())
+        }
+    }
+
+    fn parse(&self, html: &str) -> Html {
+        let mut document = if self.parse_xml {
+            Html::parse_document(&xml::to_html(html))
+        } else {
+            Html::parse_document(html)
+        };
+        self.remove_hidden(&mut document);
+        document
+    }
+
+    fn is_hidden(&self, node: &Node) -> bool {
+        match node {
+            Node::Element(el) => el.classes().any(|class| self.hidden_classes.iter().any(|c| c == class)),
+            _ => false,
+        }
+    }
+
+    fn has_hidden(&self, document: &Html) -> bool {
+        // Detached nodes stay in the tree, so only look at the attached ones
+        !self.hidden_classes.is_empty() && document.tree.root().descendants().any(|node| self.is_hidden(node.value()))
+    }
+
+    // Detaches the elements with one of the hidden classes, and so everything
+    // inside them
+    fn remove_hidden(&self, document: &mut Html) {
+        if self.hidden_classes.is_empty() {
+            return;
+        }
+        let hidden: Vec<_> = document
+            .tree
+            .nodes()
+            .filter(|node| self.is_hidden(node.value()))
+            .map(|node| node.id())
+            .collect();
+        for id in hidden {
+            if let Some(mut node) = document.tree.get_mut(id) {
+                node.detach();
+            }
+        }
+    }
+
+    fn visitor(&self) -> ScraperVisitor {
+        ScraperVisitor {
+            base_url: self.base_url.clone(),
+            cleaners: self.cleaners.clone(),
+            keep_script_style: self.keep_script_style,
+            normalize_attribute_names: self.normalize_attribute_names,
+            boolean_attributes: self.boolean_attributes.clone(),
+            max_total_matches: self.max_total_matches,
+            raw_text: self.raw_text,
+            preserve_preformatted: self.preserve_preformatted,
+            element_filter: self.element_filter.clone(),
+            selectors: self.selectors.clone(),
+            ..Default::default()
+        }
+    }
+
+    // Evaluates an `All` rule with `sub_rules` with a thread per match. Parsed
+    // documents can't be shared between threads, so every match is
+    // serialized and parsed again on its own. Returns `None` for rules this
+    // doesn't apply to, including matches that don't survive the round trip
+    // as a fragment, like table rows.
+    #[cfg(feature = "parallel")]
+    fn visit_sections(
+        &self,
+        visitor: &mut ScraperVisitor,
+        document: &Html,
+        rule: &ScrapeRule,
+    ) -> Result<Option<HashMap<String, String>>, ScrapeError> {
+        use rayon::prelude::*;
+
+        let ScrapeRule::All {
+            selector,
+            sub_rules: Some(sub_rules),
+            with_section: false,
+            contains: None,
+            where_attr: None,
+            offset: None,
+            limit: None,
+            dedup: false,
+            options: crate::scraper_config::RuleOptions { cleaner: None, .. },
+            ..
+        } = rule
+        else {
+            return Ok(None);
         };
+        // These track state across the whole document
+        if visitor.sources.is_some() || visitor.max_total_matches.is_some() || visitor.tags.is_some() {
+            return Ok(None);
+        }
+
+        let compiled = visitor.selectors.get(selector)?;
+        let sections: Vec<(String, String)> = select(&document.root_element(), &compiled)
+            .filter(|section| visitor.accepts(section))
+            .map(|section| (section.value().name().to_string(), section.html()))
+            .collect();
+        // Nothing to spread over threads, and `required` is checked serially
+        if sections.is_empty() {
+            return Ok(None);
+        }
 
-        let document = Html::parse_document(html);
-        let mut visitor = ScraperVisitor;
+        let values = sections
+            .par_iter()
+            .map(|(tag, html)| {
+                let fragment = Html::parse_fragment(html);
+                let section = fragment.root_element().child_elements().next();
+                let Some(section) = section.filter(|section| section.value().name() == tag) else {
+                    return Ok(None);
+                };
+                let mut visitor = self.visitor();
+                let mut sub_result = HashMap::new();
+                for sub_rule in sub_rules {
+                    sub_result.extend(visitor.visit_element(&section, sub_rule, self.cleaner.as_deref())?);
+                }
+                Ok(Some(serde_json::to_string(&ordered_fields(sub_rules, sub_result)).unwrap()))
+            })
+            .collect::<Result<Option<Vec<String>>, ScrapeError>>()?;
+
+        Ok(values.map(|values| {
+            visitor.total_matches += values.len();
+            HashMap::from([(rule.output_name().to_string(), serde_json::to_string(&values).unwrap())])
+        }))
+    }
+
+    fn visit_document(
+        &self,
+        visitor: &mut ScraperVisitor,
+        scraper_config: &ScraperConfig,
+        html: &str,
+    ) -> Result<HashMap<String, String>, ScrapeError> {
+        self.visit_parsed(visitor, scraper_config, &self.parse(html))
+    }
+
+    fn visit_parsed(
+        &self,
+        visitor: &mut ScraperVisitor,
+        scraper_config: &ScraperConfig,
+        document: &Html,
+    ) -> Result<HashMap<String, String>, ScrapeError> {
         let mut result = HashMap::new();
+        let mut unmatched = Vec::new();
 
-        for rule in scraper_config.rules {
-            result.extend(visitor.visit_element(
-                &document.root_element(),
-                &rule,
-                self.cleaner.as_deref(),
-            ));
+        for rule in &scraper_config.rules {
+            #[cfg(feature = "parallel")]
+            let sections = match self.parallel_sections {
+                true => self.visit_sections(visitor, document, rule)?,
+                false => None,
+            };
+            #[cfg(not(feature = "parallel"))]
+            let sections = None;
+            // Sections are only scraped in parallel when some matched
+            let (fields, matched) = match sections {
+                Some(fields) => (fields, true),
+                None => {
+                    let fields = visitor.visit_element(&document.root_element(), rule, self.cleaner.as_deref())?;
+                    (fields, visitor.matched)
+                }
+            };
+            let empty = fields.get(rule.output_name()).is_some_and(|value| value.trim().is_empty());
+            let optional = matches!(rule, ScrapeRule::Optional { .. });
+            if self.strict && !optional && (!matched || empty) {
+                unmatched.push(rule.name().to_string());
+            }
+            result.extend(fields);
         }
+        if !unmatched.is_empty() {
+            return Err(ScrapeError::Unmatched(unmatched));
+        }
+        scraper_config.validate(&result)?;
 
-        Ok(T::from(result))
+        Ok(result)
     }
 }
 
-impl Default for HtmlScraper {
-    fn default() -> Self {
-        HtmlScraper {
-            config: None,
-            cleaner: None,
+// The delay and URL of a refresh `content` value, following the HTML spec:
+// a whole number of seconds, an ignored fraction, then `;` or `,` and an
+// optionally quoted URL, itself optionally prefixed by `url=`
+fn parse_refresh(content: &str) -> Option<(u32, &str)> {
+    let content = content.trim_start();
+    let digits = content.find(|c: char| !c.is_ascii_digit()).unwrap_or(content.len());
+    let delay = content[..digits].parse().ok()?;
+
+    let rest = content[digits..].trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let rest = rest.trim_start().trim_start_matches([';', ',']).trim_start();
+    let rest = match rest.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => match rest[3..].trim_start().strip_prefix('=') {
+            Some(rest) => rest.trim_start(),
+            None => rest,
+        },
+        _ => rest,
+    };
+    let target = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let rest = &rest[1..];
+            &rest[..rest.find(quote).unwrap_or(rest.len())]
         }
+        _ => rest.trim_end(),
+    };
+
+    (!target.is_empty()).then_some((delay, target))
+}
+
+// The ancestors of `element` matching `selector`, closest first
+fn enclosing_matches<'a>(element: &ElementRef<'a>, selector: &Selector) -> Vec<ElementRef<'a>> {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .filter(|ancestor| selector.matches(ancestor))
+        .collect()
+}
+
+// Text of a heading, without the text of headings nested in it
+fn heading_text(element: &ElementRef, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(el) if matches!(el.name(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {}
+            Node::Element(el) if matches!(el.name(), "script" | "style" | "template") => {}
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    heading_text(&child, text);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
-}
\ No newline at end of file
+    hash
+}
@@ -0,0 +1,111 @@
+// A small JSONPath subset: `$`, `.name`, `['name']`, `[n]` (negative counts
+// from the end), `*` and `..` recursive descent
+
+use serde_json::Value;
+
+use crate::ScrapeError;
+
+enum Selector {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+struct Segment {
+    descendant: bool,
+    selector: Selector,
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>, ScrapeError> {
+    let invalid = || ScrapeError::InvalidJsonPath(path.to_string());
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+
+    while !rest.is_empty() {
+        let descendant = rest.starts_with("..");
+        if descendant {
+            rest = &rest[2..];
+        } else if let Some(r) = rest.strip_prefix('.') {
+            rest = r;
+        } else if !rest.starts_with('[') {
+            return Err(invalid());
+        }
+
+        let selector = if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']').ok_or_else(invalid)?;
+            let inner = r[..end].trim();
+            rest = &r[end + 1..];
+            if inner == "*" {
+                Selector::Wildcard
+            } else if let Some(key) = quoted(inner) {
+                Selector::Key(key.to_string())
+            } else {
+                Selector::Index(inner.parse().map_err(|_| invalid())?)
+            }
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = &rest[end..];
+            match name {
+                "" => return Err(invalid()),
+                "*" => Selector::Wildcard,
+                name => Selector::Key(name.to_string()),
+            }
+        };
+
+        segments.push(Segment {
+            descendant,
+            selector,
+        });
+    }
+
+    Ok(segments)
+}
+
+fn quoted(s: &str) -> Option<&str> {
+    s.strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .or_else(|| s.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+}
+
+fn descendants<'a>(value: &'a Value, out: &mut Vec<&'a Value>) {
+    out.push(value);
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| descendants(item, out)),
+        Value::Object(map) => map.values().for_each(|item| descendants(item, out)),
+        _ => {}
+    }
+}
+
+fn select<'a>(selector: &Selector, value: &'a Value, out: &mut Vec<&'a Value>) {
+    match (selector, value) {
+        (Selector::Key(key), Value::Object(map)) => out.extend(map.get(key)),
+        (Selector::Index(i), Value::Array(items)) => {
+            let i = if *i < 0 { items.len() as i64 + i } else { *i };
+            out.extend(usize::try_from(i).ok().and_then(|i| items.get(i)));
+        }
+        (Selector::Wildcard, Value::Array(items)) => out.extend(items),
+        (Selector::Wildcard, Value::Object(map)) => out.extend(map.values()),
+        _ => {}
+    }
+}
+
+/// Every value in `root` matched by `path`
+pub(crate) fn query(root: &Value, path: &str) -> Result<Vec<Value>, ScrapeError> {
+    let mut nodes = vec![root];
+    for segment in parse(path)? {
+        let candidates = if segment.descendant {
+            let mut all = Vec::new();
+            nodes.iter().for_each(|node| descendants(node, &mut all));
+            all
+        } else {
+            nodes
+        };
+        let mut matched = Vec::new();
+        for node in candidates {
+            select(&segment.selector, node, &mut matched);
+        }
+        nodes = matched;
+    }
+    Ok(nodes.into_iter().cloned().collect())
+}
@@ -3,14 +3,30 @@ mod scraper_config;
 mod visitor;
 mod html_scraper;
 mod error;
+mod url;
+mod output;
+mod element;
+mod table;
+mod data_uri;
+mod de;
+mod xml;
+#[cfg(feature = "jsonpath")]
+mod jsonpath;
 
 
-pub use cleaner::{DefaultCleaner, TextCleaner};
-pub use scraper_config::{ScrapeRule, ScraperConfig, ScrapeConfig};
+pub use cleaner::{ChainCleaner, DefaultCleaner, EntityDecodeCleaner, ListMarkerCleaner, LowercaseCleaner, NumberCleaner, TextCleaner, UppercaseCleaner};
+#[cfg(feature = "regex")]
+pub use cleaner::RegexReplaceCleaner;
+pub use scraper_config::{AttributeDecode, Coerce, ExtractKind, ResolveStep, RuleOptions, ScrapeRule, ScraperConfig, ScraperConfigBuilder, ScrapeConfig, Validator};
 
 
-pub use visitor::{ScraperVisitor, Visitor};
+pub use visitor::{ElementFilter, FieldResult, ScraperVisitor, Visitor};
+pub use element::ElementExt;
+pub use table::Table;
 
 
-pub use html_scraper::{HtmlScraper, HtmlScraperBuilder};
-pub use error::ConfigError;
\ No newline at end of file
+pub use html_scraper::{CommentRecord, HtmlScraper, HtmlScraperBuilder, RuleReport, ValidationReport, HTML_BOOLEAN_ATTRIBUTES};
+#[cfg(feature = "regex")]
+pub use html_scraper::RegexLimits;
+pub use error::{ConfigError, ScrapeError};
+pub use output::{to_csv, to_pretty_string, to_tsv};
\ No newline at end of file
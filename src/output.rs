@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+// The union of all field names, sorted so the column order is stable
+fn headers(records: &[HashMap<String, String>]) -> Vec<&str> {
+    let mut headers: Vec<&str> = records
+        .iter()
+        .flat_map(|record| record.keys().map(String::as_str))
+        .collect();
+    headers.sort_unstable();
+    headers.dedup();
+    headers
+}
+
+fn write_delimited(
+    records: &[HashMap<String, String>],
+    delimiter: char,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let headers = headers(records);
+    let mut out = String::new();
+
+    let header_row: Vec<String> = headers.iter().map(|h| escape(h)).collect();
+    out.push_str(&header_row.join(&delimiter.to_string()));
+    out.push('\n');
+
+    for record in records {
+        let row: Vec<String> = headers
+            .iter()
+            .map(|h| escape(record.get(*h).map(String::as_str).unwrap_or("")))
+            .collect();
+        out.push_str(&row.join(&delimiter.to_string()));
+        out.push('\n');
+    }
+    out
+}
+
+/// Writes scraped records as CSV with a header row. Fields containing a
+/// comma, quote or line break are quoted.
+pub fn to_csv(records: &[HashMap<String, String>]) -> String {
+    write_delimited(records, ',', |field| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    })
+}
+
+/// Writes scraped records as tab-separated values with a header row.
+/// Tabs, line breaks and backslashes in fields are escaped as `\t`, `\n`,
+/// `\r` and `\\`.
+pub fn to_tsv(records: &[HashMap<String, String>]) -> String {
+    write_delimited(records, '\t', |field| {
+        let mut escaped = String::with_capacity(field.len());
+        for c in field.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    })
+}
+
+/// Pretty-prints a scrape result, e.g. from `HtmlScraper::scrape_value`.
+/// Fields keep their order, so the same result always prints the same,
+/// byte for byte, for caching and diffing.
+pub fn to_pretty_string(value: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(value).unwrap()
+}
@@ -1,8 +1,11 @@
 
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, fs, path::Path,};
+use serde_json::Value;
+use std::{borrow::Cow, collections::{HashMap, HashSet}, fmt::{self, Debug, Display}, fs, path::Path, sync::Arc};
 
-use crate::ConfigError;
+use crate::{visitor::SelectorCache, ConfigError, ScrapeError};
+#[cfg(feature = "regex")]
+use crate::RegexLimits;
 
 pub trait ScrapeConfig: for<'de> Deserialize<'de> + Sized {
     fn get_config() -> ScraperConfig;
@@ -22,26 +25,56 @@ pub trait ScrapeConfig: for<'de> Deserialize<'de> + Sized {
                     Err(ConfigError::TomlNotEnabled)
                 }
             } else {
-                Err(ConfigError::UnsupportedFormat)
+                // Unknown extension, detect the format from the content instead
+                detect_format(&config_content)
             }
         } else {
-            // Try parsing as JSON first, then TOML if that fails and the feature is enabled
-            serde_json::from_str(config).or_else(|_| {
-                #[cfg(feature = "toml_config")]
-                {
-                    toml::from_str(config).map_err(|e| e.into())
-                }
-                #[cfg(not(feature = "toml_config"))]
-                {
-                    Err(ConfigError::UnsupportedFormat)
-                }
-            })
+            detect_format(config)
         }
     }
 }
 
+// Try parsing as JSON first, then TOML if that fails, the feature is enabled
+// and the config looks like TOML; anything else is an unsupported format
+fn detect_format(config: &str) -> Result<ScraperConfig, ConfigError> {
+    let json = serde_json::from_str(config);
+    #[cfg(feature = "toml_config")]
+    {
+        json.or_else(|_| match looks_like_toml(config) {
+            true => toml::from_str(config).map_err(|e| e.into()),
+            false => Err(ConfigError::UnsupportedFormat),
+        })
+    }
+    #[cfg(not(feature = "toml_config"))]
+    {
+        json.map_err(|_| ConfigError::UnsupportedFormat)
+    }
+}
+
+// The first line that isn't blank or a comment is a table header or a
+// `key = value` pair
+#[cfg(feature = "toml_config")]
+fn looks_like_toml(config: &str) -> bool {
+    config
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|line| line.starts_with('[') || line.split_once('=').is_some_and(|(key, _)| !key.trim().is_empty()))
+}
 
 
+
+/// A single extraction step.
+///
+/// Selectors are CSS selectors as understood by the `scraper` crate, plus
+/// `h*` for a heading of any level, `h1` to `h6`, e.g. `article > h*`.
+/// Attribute values compare case-sensitively, unless the selector carries
+/// the `i` flag, e.g. `[data-state="active" i]` also matches `"Active"`.
+///
+/// An attribute repeated on one element, e.g. `class="a" class="b"` in
+/// malformed markup, keeps its first value: the HTML parser drops the
+/// repeats, as the HTML spec requires, so they can't be selected or
+/// extracted.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ScrapeRule {
@@ -52,6 +85,51 @@ pub enum ScrapeRule {
         sub_rules: Option<Vec<ScrapeRule>>,
         #[serde(default)]
         attribute: Option<String>,
+        /// What to read from the match when there is no `attribute`: its
+        /// text, its markup or an attribute
+        #[serde(default)]
+        extract: ExtractKind,
+        /// Only consider matches whose text contains this, ignoring case,
+        /// e.g. the `div` mentioning "Price", which CSS can't select
+        #[serde(default)]
+        contains: Option<String>,
+        /// Only consider matches whose attribute, the first of the pair, has
+        /// exactly this value, the second, e.g. `["rel", "next"]`. Matches
+        /// without the attribute are left out.
+        #[serde(default)]
+        where_attr: Option<(String, String)>,
+        /// Read `attribute` from the first element matching this selector,
+        /// the match itself or one of its descendants, instead of the match,
+        /// e.g. the `src` of the `img` inside each `.product`
+        #[serde(default)]
+        attribute_from: Option<String>,
+        /// Decode the attribute value instead of cleaning it
+        #[serde(default)]
+        decode: Option<AttributeDecode>,
+        /// Parse the value as this type, failing with
+        /// `ScrapeError::CoercionFailed` if it doesn't; `scrape_value` then
+        /// stores it typed, e.g. as a number
+        #[serde(default)]
+        coerce: Option<Coerce>,
+        /// Fail with `ScrapeError::RelativeUrl` if the attribute value isn't an
+        /// absolute URL and can't be resolved against the scraper's base URL
+        #[serde(default)]
+        require_absolute_url: bool,
+        /// Resolve a relative attribute value against the scraper's base URL
+        /// if it has one, leaving it as it is otherwise
+        #[serde(default)]
+        resolve_url: bool,
+        /// Store `{ "value": ..., "length": ... }` with the length in
+        /// characters instead of the plain value, e.g. to spot truncation.
+        /// Has no effect with `sub_rules`.
+        #[serde(default)]
+        with_length: bool,
+        /// Stored, cleaned, under `name` when nothing matches, so the field
+        /// is always present
+        #[serde(default)]
+        default: Option<String>,
+        #[serde(flatten)]
+        options: RuleOptions,
     },
     All {
         selector: String,
@@ -60,32 +138,841 @@ pub enum ScrapeRule {
         sub_rules: Option<Vec<ScrapeRule>>,
         #[serde(default)]
         attribute: Option<String>,
+        /// What to read from the match when there is no `attribute`: its
+        /// text, its markup or an attribute
+        #[serde(default)]
+        extract: ExtractKind,
+        /// Only keep matches whose text contains this, ignoring case
+        #[serde(default)]
+        contains: Option<String>,
+        /// Only keep matches whose attribute, the first of the pair, has
+        /// exactly this value, the second, e.g. `["rel", "next"]`. Matches
+        /// without the attribute are left out.
+        #[serde(default)]
+        where_attr: Option<(String, String)>,
+        /// Read `attribute` from the first element matching this selector,
+        /// the match itself or one of its descendants, instead of the match,
+        /// e.g. the `src` of the `img` inside each `.product`
+        #[serde(default)]
+        attribute_from: Option<String>,
+        /// Decode the attribute value instead of cleaning it
+        #[serde(default)]
+        decode: Option<AttributeDecode>,
+        /// Parse each value as this type, failing with
+        /// `ScrapeError::CoercionFailed` if it doesn't; `scrape_value` then
+        /// stores it typed, e.g. as a number
+        #[serde(default)]
+        coerce: Option<Coerce>,
+        /// Fail with `ScrapeError::RelativeUrl` if the attribute value isn't an
+        /// absolute URL and can't be resolved against the scraper's base URL
+        #[serde(default)]
+        require_absolute_url: bool,
+        /// Resolve a relative attribute value against the scraper's base URL
+        /// if it has one, leaving it as it is otherwise
+        #[serde(default)]
+        resolve_url: bool,
+        /// Store `{ "value": ..., "length": ... }` with the length in
+        /// characters instead of the plain value, e.g. to spot truncation.
+        /// Has no effect with `sub_rules`.
+        #[serde(default)]
+        with_length: bool,
+        /// Add the text of the closest heading before each match in document
+        /// order, `<h1>` to `<h6>`, as `"section"`: a field of each object
+        /// with `sub_rules`, otherwise alongside `"value"` like `with_length`.
+        /// Left out for matches before the first heading.
+        #[serde(default)]
+        with_section: bool,
+        /// Leave out this many matches first, e.g. for the second page of
+        /// results. Past the last match the array is empty.
+        #[serde(default)]
+        offset: Option<usize>,
+        /// Keep at most this many matches, after the `offset`. The others
+        /// aren't extracted at all.
+        #[serde(default)]
+        limit: Option<usize>,
+        /// Drop values already seen, keeping the first of each. Values are
+        /// compared after cleaning.
+        #[serde(default)]
+        dedup: bool,
+        /// Stored, cleaned, as the only element when nothing matches
+        #[serde(default)]
+        default: Option<String>,
+        #[serde(flatten)]
+        options: RuleOptions,
     },
+    /// The text of all matches, joined with a space.
     Text {
         selector: String,
         name: String,
+        /// Join the cleaned text of each match with this separator instead,
+        /// skipping empty ones, e.g. `"\n\n"` for markdown paragraphs
+        #[serde(default)]
+        paragraph_separator: Option<String>,
+        /// Join the text of each match, cleaned on its own, with this
+        /// separator instead of a space, keeping empty ones, e.g. `", "` for
+        /// list items. `paragraph_separator` takes precedence.
+        #[serde(default)]
+        separator: Option<String>,
+        /// Parse the value as this type, failing with
+        /// `ScrapeError::CoercionFailed` if it doesn't; `scrape_value` then
+        /// stores it typed, e.g. as a number
+        #[serde(default)]
+        coerce: Option<Coerce>,
+        /// Leave out this many matches first, e.g. 1 to skip a lead paragraph
+        #[serde(default)]
+        skip: usize,
+        /// Stored, cleaned, instead of an empty string when nothing matches
+        #[serde(default)]
+        default: Option<String>,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// Collects the `itemprop` properties of the first `itemscope` element
+    /// matching `scope_selector` into an object keyed by property name
+    Microdata {
+        scope_selector: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// Reads the current value of the first matching form control: the
+    /// `value` of an `<input>`, the text of a `<textarea>` or the value of
+    /// the selected `<option>` of a `<select>`
+    InputValue {
+        selector: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// For every match, the text of its next sibling element, e.g. the
+    /// `<dd>` following each matched `<dt>`. Stored as an array.
+    NextSiblingText {
+        selector: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// Extracts numbered markup such as `#row-1`, `#row-2`, ... by
+    /// substituting `{i}` in `selector_template` with 1 through `count`.
+    /// Stored as an array with an entry per index that matched.
+    Repeat {
+        selector_template: String,
+        name: String,
+        count: usize,
+        #[serde(default)]
+        sub_rules: Option<Vec<ScrapeRule>>,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// For every match, walks up to the nearest ancestor matching
+    /// `ancestor_selector` (like DOM `closest()`) and extracts its attribute,
+    /// or its text without one. Stored as an array.
+    ClosestAncestor {
+        selector: String,
+        ancestor_selector: String,
+        name: String,
+        #[serde(default)]
+        attribute: Option<String>,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// The text directly inside the first match, leaving out the text of
+    /// child elements. With `index`, only that direct text node (counting
+    /// from 0 and ignoring whitespace-only ones), e.g. index 0 of
+    /// `<div>prefix: <b>value</b> suffix</div>` is `"prefix: "`.
+    OwnText {
+        selector: String,
+        name: String,
+        #[serde(default)]
+        index: Option<usize>,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// An object built from key/value attribute pairs on the matches, e.g.
+    /// `data-key`/`data-value`. Matches lacking either attribute are skipped
+    /// and the first match wins for a repeated key.
+    DynamicKey {
+        selector: String,
+        key_attribute: String,
+        value_attribute: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// Evaluates each candidate in order and stores the first non-empty
+    /// value of a candidate that matched under `name`, so an `Exists` that
+    /// found nothing or a candidate's `default` doesn't win. Candidates are
+    /// looked up by their own name.
+    Coalesce {
+        name: String,
+        candidates: Vec<ScrapeRule>,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// Tries each step in order and stores the first non-empty value, the
+    /// step's attribute if it has one and its text otherwise, or else
+    /// `default`, cleaned
+    Resolve {
+        name: String,
+        steps: Vec<ResolveStep>,
+        #[serde(default)]
+        default: Option<String>,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// Like `One`, reading the first element matched by the first of
+    /// `selectors` that matches anything, e.g. for a title that's `h1.title`
+    /// on one variant of a page and `.headline h1` on another
+    OneOf {
+        selectors: Vec<String>,
+        name: String,
+        #[serde(default)]
+        attribute: Option<String>,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// The number of elements matching `selector`, without reading their
+    /// text. Fails `required` when there are none.
+    Count {
+        selector: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// `"true"` if any element matches `selector`, `"false"` otherwise
+    Exists {
+        selector: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// The items of the first list matching `selector` as a JSON array,
+    /// each item's text followed by an array for any list nested in it,
+    /// e.g. `["a", ["a.1", "a.2"], "b"]`
+    ListTree {
+        selector: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
     },
+    /// Several attributes of the first element matching `selector`, as a
+    /// JSON object keyed by attribute name in the order given
+    Attributes {
+        selector: String,
+        name: String,
+        attributes: Vec<String>,
+        /// Leave out attributes the element doesn't have, instead of
+        /// storing them as empty strings
+        #[serde(default)]
+        skip_missing: bool,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// The text and `href` of every element matching `selector`, usually
+    /// `<a>`s, as a JSON array of `{ "text": ..., "href": ... }` objects.
+    /// Elements without an `href` are left out.
+    Links {
+        selector: String,
+        name: String,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// The `src` and `alt` of every element matching `selector`, usually
+    /// `<img>`s, as a JSON array of `{ "src": ..., "alt": ... }` objects.
+    /// Elements without a `src` are left out; a missing `alt` is empty.
+    Images {
+        selector: String,
+        name: String,
+        /// Resolve a relative `src` against the scraper's base URL if it has
+        /// one, see `resolve_url` on `One`
+        #[serde(default)]
+        resolve_url: bool,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// The rows of the first `<table>` matching `selector`, as a JSON array
+    /// of cell arrays, padded with empty strings to the widest row. Cells
+    /// are read like in `HtmlScraper::scrape_typed_table`.
+    Table {
+        selector: String,
+        name: String,
+        /// Use the first row's cells as keys, storing the other rows as
+        /// objects instead. Rows are padded to the header's width and cells
+        /// past it are left out.
+        #[serde(default)]
+        header: bool,
+        #[serde(flatten)]
+        options: RuleOptions,
+    },
+    /// Evaluates `rule`, leaving its fields out instead of failing the
+    /// scrape when its matches can't be extracted, e.g. a relative URL under
+    /// `require_absolute_url` or a malformed `data:` URI, or when it is
+    /// `required` and missing. Errors in the config itself and the match
+    /// limit still abort.
+    Optional { rule: Box<ScrapeRule> },
+}
+
+impl ScrapeRule {
+    /// A `One` rule reading the text of the first match, to be refined with
+    /// methods like `attribute` instead of spelling out every field
+    pub fn one(selector: &str, name: &str) -> Self {
+        ScrapeRule::One {
+            selector: selector.to_string(),
+            name: name.to_string(),
+            sub_rules: None,
+            attribute: None,
+            extract: ExtractKind::Text,
+            contains: None,
+            where_attr: None,
+            require_absolute_url: false,
+            resolve_url: false,
+            attribute_from: None,
+            decode: None,
+            coerce: None,
+            with_length: false,
+            default: None,
+            options: RuleOptions::default(),
+        }
+    }
+
+    /// An `All` rule reading the text of every match, see `one`
+    pub fn all(selector: &str, name: &str) -> Self {
+        ScrapeRule::All {
+            selector: selector.to_string(),
+            name: name.to_string(),
+            sub_rules: None,
+            attribute: None,
+            extract: ExtractKind::Text,
+            contains: None,
+            where_attr: None,
+            require_absolute_url: false,
+            resolve_url: false,
+            attribute_from: None,
+            decode: None,
+            coerce: None,
+            with_length: false,
+            with_section: false,
+            offset: None,
+            limit: None,
+            dedup: false,
+            default: None,
+            options: RuleOptions::default(),
+        }
+    }
+
+    /// A `Text` rule joining the text of every match, see `one`
+    pub fn text(selector: &str, name: &str) -> Self {
+        ScrapeRule::Text {
+            selector: selector.to_string(),
+            name: name.to_string(),
+            paragraph_separator: None,
+            separator: None,
+            coerce: None,
+            skip: 0,
+            default: None,
+            options: RuleOptions::default(),
+        }
+    }
+
+    /// Sets the `attribute` of `One`, `All` and `ClosestAncestor` rules.
+    /// Other rules are returned unchanged.
+    pub fn attribute(mut self, attr: &str) -> Self {
+        if let ScrapeRule::One { attribute, .. }
+        | ScrapeRule::All { attribute, .. }
+        | ScrapeRule::ClosestAncestor { attribute, .. } = &mut self
+        {
+            *attribute = Some(attr.to_string());
+        }
+        self
+    }
+
+    /// Sets the `coerce` type of `One`, `All` and `Text` rules. Other rules
+    /// are returned unchanged.
+    pub fn coerce(mut self, to: Coerce) -> Self {
+        if let ScrapeRule::One { coerce, .. } | ScrapeRule::All { coerce, .. } | ScrapeRule::Text { coerce, .. } =
+            &mut self
+        {
+            *coerce = Some(to);
+        }
+        self
+    }
+
+    /// Sets the `sub_rules` of `One`, `All` and `Repeat` rules. Other rules
+    /// are returned unchanged.
+    pub fn sub_rules(mut self, rules: Vec<ScrapeRule>) -> Self {
+        if let ScrapeRule::One { sub_rules, .. }
+        | ScrapeRule::All { sub_rules, .. }
+        | ScrapeRule::Repeat { sub_rules, .. } = &mut self
+        {
+            *sub_rules = Some(rules);
+        }
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ScrapeRule::One { name, .. }
+            | ScrapeRule::All { name, .. }
+            | ScrapeRule::Text { name, .. }
+            | ScrapeRule::Microdata { name, .. }
+            | ScrapeRule::InputValue { name, .. }
+            | ScrapeRule::NextSiblingText { name, .. }
+            | ScrapeRule::Repeat { name, .. }
+            | ScrapeRule::ClosestAncestor { name, .. }
+            | ScrapeRule::OwnText { name, .. }
+            | ScrapeRule::DynamicKey { name, .. }
+            | ScrapeRule::Coalesce { name, .. }
+            | ScrapeRule::Resolve { name, .. }
+            | ScrapeRule::Count { name, .. }
+            | ScrapeRule::Exists { name, .. }
+            | ScrapeRule::ListTree { name, .. }
+            | ScrapeRule::Attributes { name, .. }
+            | ScrapeRule::Links { name, .. }
+            | ScrapeRule::Images { name, .. }
+            | ScrapeRule::Table { name, .. }
+            | ScrapeRule::OneOf { name, .. } => name,
+            ScrapeRule::Optional { rule } => rule.name(),
+        }
+    }
+
+    /// The key the rule's field is stored under: `output_name` if set,
+    /// otherwise `name`
+    pub fn output_name(&self) -> &str {
+        self.options().output_name.as_deref().unwrap_or(self.name())
+    }
+
+    /// The name of the registered cleaner this rule uses, if any
+    pub fn cleaner(&self) -> Option<&str> {
+        match self {
+            // These don't read any text
+            ScrapeRule::Count { .. } | ScrapeRule::Exists { .. } => None,
+            rule => rule.options().cleaner.as_deref(),
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.options().tags
+    }
+
+    pub fn required(&self) -> bool {
+        self.options().required
+    }
+
+    /// The `required`, `output_name`, `cleaner` and `tags` of the rule, or of
+    /// the rule an `Optional` wraps
+    pub fn options(&self) -> &RuleOptions {
+        match self {
+            ScrapeRule::One { options, .. }
+            | ScrapeRule::All { options, .. }
+            | ScrapeRule::Text { options, .. }
+            | ScrapeRule::Microdata { options, .. }
+            | ScrapeRule::InputValue { options, .. }
+            | ScrapeRule::NextSiblingText { options, .. }
+            | ScrapeRule::Repeat { options, .. }
+            | ScrapeRule::ClosestAncestor { options, .. }
+            | ScrapeRule::OwnText { options, .. }
+            | ScrapeRule::DynamicKey { options, .. }
+            | ScrapeRule::Coalesce { options, .. }
+            | ScrapeRule::Resolve { options, .. }
+            | ScrapeRule::Count { options, .. }
+            | ScrapeRule::Exists { options, .. }
+            | ScrapeRule::ListTree { options, .. }
+            | ScrapeRule::Attributes { options, .. }
+            | ScrapeRule::Links { options, .. }
+            | ScrapeRule::Images { options, .. }
+            | ScrapeRule::Table { options, .. }
+            | ScrapeRule::OneOf { options, .. } => options,
+            ScrapeRule::Optional { rule } => rule.options(),
+        }
+    }
+
+    /// Like `options`, to set them on a rule from a constructor like `one`
+    pub fn options_mut(&mut self) -> &mut RuleOptions {
+        match self {
+            ScrapeRule::One { options, .. }
+            | ScrapeRule::All { options, .. }
+            | ScrapeRule::Text { options, .. }
+            | ScrapeRule::Microdata { options, .. }
+            | ScrapeRule::InputValue { options, .. }
+            | ScrapeRule::NextSiblingText { options, .. }
+            | ScrapeRule::Repeat { options, .. }
+            | ScrapeRule::ClosestAncestor { options, .. }
+            | ScrapeRule::OwnText { options, .. }
+            | ScrapeRule::DynamicKey { options, .. }
+            | ScrapeRule::Coalesce { options, .. }
+            | ScrapeRule::Resolve { options, .. }
+            | ScrapeRule::Count { options, .. }
+            | ScrapeRule::Exists { options, .. }
+            | ScrapeRule::ListTree { options, .. }
+            | ScrapeRule::Attributes { options, .. }
+            | ScrapeRule::Links { options, .. }
+            | ScrapeRule::Images { options, .. }
+            | ScrapeRule::Table { options, .. }
+            | ScrapeRule::OneOf { options, .. } => options,
+            ScrapeRule::Optional { rule } => rule.options_mut(),
+        }
+    }
+
+    /// The fields this rule produces, in order. `One` with `sub_rules` puts
+    /// the fields of its sub-rules in place of its own.
+    pub(crate) fn field_names(&self) -> Vec<&str> {
+        self.field_rules().into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// Like `field_names`, with the rule that stores each field. Fields are
+    /// named by their `output_name`.
+    pub(crate) fn field_rules(&self) -> Vec<(&str, &ScrapeRule)> {
+        match self {
+            ScrapeRule::One {
+                sub_rules: Some(sub_rules),
+                ..
+            } => sub_rules.iter().flat_map(ScrapeRule::field_rules).collect(),
+            ScrapeRule::Optional { rule } => rule.field_rules(),
+            rule => vec![(rule.output_name(), rule)],
+        }
+    }
+
+    /// Every selector used by this rule and its nested rules, depth first
+    pub(crate) fn selectors(&self) -> Vec<Cow<'_, str>> {
+        match self {
+            ScrapeRule::One {
+                selector,
+                sub_rules,
+                attribute_from,
+                ..
+            }
+            | ScrapeRule::All {
+                selector,
+                sub_rules,
+                attribute_from,
+                ..
+            } => {
+                let mut selectors = vec![Cow::from(selector)];
+                selectors.extend(attribute_from.iter().map(Cow::from));
+                for sub_rule in sub_rules.iter().flatten() {
+                    selectors.extend(sub_rule.selectors());
+                }
+                selectors
+            }
+            ScrapeRule::Repeat {
+                selector_template,
+                count,
+                sub_rules,
+                ..
+            } => {
+                let mut selectors: Vec<Cow<str>> = (1..=*count)
+                    .map(|i| Cow::from(selector_template.replace("{i}", &i.to_string())))
+                    .collect();
+                for sub_rule in sub_rules.iter().flatten() {
+                    selectors.extend(sub_rule.selectors());
+                }
+                selectors
+            }
+            ScrapeRule::Text { selector, .. }
+            | ScrapeRule::InputValue { selector, .. }
+            | ScrapeRule::NextSiblingText { selector, .. }
+            | ScrapeRule::OwnText { selector, .. }
+            | ScrapeRule::DynamicKey { selector, .. }
+            | ScrapeRule::Count { selector, .. }
+            | ScrapeRule::Exists { selector, .. }
+            | ScrapeRule::ListTree { selector, .. }
+            | ScrapeRule::Attributes { selector, .. }
+            | ScrapeRule::Links { selector, .. }
+            | ScrapeRule::Images { selector, .. }
+            | ScrapeRule::Table { selector, .. } => vec![Cow::from(selector)],
+            ScrapeRule::Microdata { scope_selector, .. } => vec![Cow::from(scope_selector)],
+            ScrapeRule::ClosestAncestor {
+                selector,
+                ancestor_selector,
+                ..
+            } => vec![Cow::from(selector), Cow::from(ancestor_selector)],
+            ScrapeRule::Coalesce { candidates, .. } => {
+                candidates.iter().flat_map(ScrapeRule::selectors).collect()
+            }
+            ScrapeRule::Resolve { steps, .. } => steps.iter().map(|step| Cow::from(&step.selector)).collect(),
+            ScrapeRule::OneOf { selectors, .. } => selectors.iter().map(Cow::from).collect(),
+            ScrapeRule::Optional { rule } => rule.selectors(),
+        }
+    }
+
+    /// The selectors that find what this rule reads, leaving out nested
+    /// rules and helpers like `attribute_from`: one for most rules, and the
+    /// alternatives in order for `Coalesce`, `Resolve` and `OneOf`
+    pub(crate) fn primary_selectors(&self) -> Vec<Cow<'_, str>> {
+        match self {
+            ScrapeRule::Coalesce { candidates, .. } => {
+                candidates.iter().flat_map(ScrapeRule::primary_selectors).collect()
+            }
+            ScrapeRule::Resolve { steps, .. } => steps.iter().map(|step| Cow::from(&step.selector)).collect(),
+            ScrapeRule::OneOf { selectors, .. } => selectors.iter().map(Cow::from).collect(),
+            ScrapeRule::Optional { rule } => rule.primary_selectors(),
+            rule => rule.selectors().into_iter().take(1).collect(),
+        }
+    }
+}
+
+/// Builds a `ScraperConfig`, see `ScraperConfig::builder`
+#[derive(Default)]
+pub struct ScraperConfigBuilder {
+    rules: Vec<ScrapeRule>,
+}
+
+impl ScraperConfigBuilder {
+    pub fn rule(mut self, rule: ScrapeRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn build(self) -> ScraperConfig {
+        ScraperConfig::new(self.rules)
+    }
+}
+
+/// The fields every rule has besides its `name`, flattened into the rule
+/// in configs, e.g. `{ "type": "One", ..., "required": true }`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleOptions {
+    /// Fail with `ScrapeError::RequiredFieldMissing` if the rule yields no
+    /// value, e.g. nothing matches, an `All` rule would store an empty
+    /// array or no candidate of a `Coalesce` rule yields a value
+    #[serde(default)]
+    pub required: bool,
+    /// Store the field under this key instead of `name`, which still
+    /// identifies the rule, e.g. for validators
+    #[serde(default)]
+    pub output_name: Option<String>,
+    /// A cleaner registered with `HtmlScraperBuilder::register_cleaner` to
+    /// use instead of the scraper's own. Sub-rules inherit it. `Count` and
+    /// `Exists` read no text and ignore it.
+    #[serde(default)]
+    pub cleaner: Option<String>,
+    /// Labels for `HtmlScraper::scrape_tagged`
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A way of getting a value tried by `ScrapeRule::Resolve`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveStep {
+    pub selector: String,
+    /// Read this attribute of the first match instead of its text
+    #[serde(default)]
+    pub attribute: Option<String>,
+}
+
+/// A decoding applied to an extracted attribute value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttributeDecode {
+    /// Parses a `data:` URI, e.g. an inline image `src`, into
+    /// `{ "mime": ..., "base64": ... }`. The payload is base64-encoded
+    /// whether or not the URI was, since JSON can't hold raw bytes.
+    DataUri,
+}
+
+/// What `One` and `All` read from each match
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExtractKind {
+    /// The text of the match and its descendants
+    #[default]
+    Text,
+    /// The markup inside the match, e.g. to keep `<em>` in a paragraph
+    InnerHtml,
+    /// The markup of the match, including its own tag
+    OuterHtml,
+    /// The value of this attribute, the same as setting `attribute`
+    Attribute(String),
+}
+
+impl ExtractKind {
+    pub(crate) fn attribute(&self) -> Option<&String> {
+        match self {
+            ExtractKind::Attribute(attribute) => Some(attribute),
+            _ => None,
+        }
+    }
+}
+
+/// A type to parse a field into, see `coerce`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Coerce {
+    /// An integer, e.g. `"42"`
+    Int,
+    /// A floating point number, e.g. `"4.5"`
+    Float,
+    /// `true` or `false`, in any case
+    Bool,
+    /// A date, or date and time, in this `chrono` format, e.g. `"%d.%m.%Y"`,
+    /// stored in ISO 8601 as `2024-03-01` or `2024-03-01T12:30:00`
+    #[cfg(feature = "chrono")]
+    Date(String),
+}
+
+impl Coerce {
+    /// The typed `value`, `None` if it doesn't parse as one
+    pub(crate) fn parse(&self, value: &str) -> Option<Value> {
+        let value = value.trim();
+        match self {
+            Coerce::Int => value.parse::<i64>().ok().map(Value::from),
+            Coerce::Float => value.parse().ok().and_then(serde_json::Number::from_f64).map(Value::Number),
+            Coerce::Bool => match value.to_ascii_lowercase().as_str() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            #[cfg(feature = "chrono")]
+            Coerce::Date(format) => {
+                let date = match chrono::NaiveDateTime::parse_from_str(value, format) {
+                    Ok(datetime) => datetime.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    Err(_) => chrono::NaiveDate::parse_from_str(value, format).ok()?.to_string(),
+                };
+                Some(Value::String(date))
+            }
+        }
+    }
+}
+
+/// A check on the final value of a field, see `ScraperConfig::with_validators`
+#[derive(Clone)]
+pub enum Validator {
+    /// Passes if the regex matches anywhere in the value; anchor it with
+    /// `^...$` to require a full match
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+    Fn(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl Validator {
+    pub fn from_fn<F: Fn(&str) -> bool + Send + Sync + 'static>(f: F) -> Self {
+        Validator::Fn(Arc::new(f))
+    }
+
+    /// A `Regex` validator compiling `pattern` within the default
+    /// `RegexLimits`. `HtmlScraperBuilder::try_build` checks it against the
+    /// builder's limits.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self, ScrapeError> {
+        Ok(Validator::Regex(RegexLimits::default().compile(pattern)?))
+    }
+
+    pub fn is_valid(&self, value: &str) -> bool {
+        match self {
+            #[cfg(feature = "regex")]
+            Validator::Regex(regex) => regex.is_match(value),
+            Validator::Fn(f) => f(value),
+        }
+    }
+}
+
+impl Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "regex")]
+            Validator::Regex(regex) => write!(f, "Validator::Regex({})", regex.as_str()),
+            Validator::Fn(_) => write!(f, "Validator::Fn"),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScraperConfig {
     pub(crate) rules: Vec<ScrapeRule>,
+    // Code-only, so not part of JSON/TOML configs
+    #[serde(skip)]
+    pub(crate) validators: HashMap<String, Validator>,
 }
 
 impl ScraperConfig {
     pub fn new(rules: Vec<ScrapeRule>) -> Self {
-        ScraperConfig { rules }
+        ScraperConfig {
+            rules,
+            validators: HashMap::new(),
+        }
+    }
+
+    /// Collects rules one at a time, e.g.
+    /// `ScraperConfig::builder().rule(ScrapeRule::one("h1", "title")).build()`
+    pub fn builder() -> ScraperConfigBuilder {
+        ScraperConfigBuilder::default()
+    }
+
+    /// Validators checked against the fields of the rules named by the keys
+    /// once a document is scraped. A failing value aborts the scrape with
+    /// `ScrapeError::ValidationFailed`; fields that weren't extracted
+    /// aren't checked. A key must name a rule whose field is stored at the
+    /// top of the result, a top-level rule or a sub-rule of a `One`, or the
+    /// scrape fails with `ScrapeError::UnknownValidator`.
+    pub fn with_validators(mut self, validators: HashMap<String, Validator>) -> Self {
+        self.validators = validators;
+        self
+    }
+
+    // Checks the fields in rule order, so the first failure reported doesn't
+    // depend on the order of the validators map
+    pub(crate) fn validate(&self, fields: &HashMap<String, String>) -> Result<(), ScrapeError> {
+        let rules: Vec<_> = self.rules.iter().flat_map(ScrapeRule::field_rules).collect();
+        let mut unknown: Vec<&String> = self
+            .validators
+            .keys()
+            .filter(|name| !rules.iter().any(|(_, rule)| rule.name() == name.as_str()))
+            .collect();
+        unknown.sort();
+        if let Some(name) = unknown.first() {
+            return Err(ScrapeError::UnknownValidator(name.to_string()));
+        }
+
+        // Validators name rules, whose fields may be stored under another key
+        let mut checked = HashSet::new();
+        for (key, rule) in rules {
+            let Some(validator) = self.validators.get(rule.name()) else {
+                continue;
+            };
+            if !checked.insert(rule.name()) {
+                continue;
+            }
+            if let Some(value) = fields.get(key) {
+                if !validator.is_valid(value) {
+                    return Err(ScrapeError::ValidationFailed {
+                        name: rule.name().to_string(),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles every selector in the config and returns the first one that
+    /// fails, so a config kept in version control can be checked in a test
+    pub fn check(&self) -> Result<(), ConfigError> {
+        self.compile(&SelectorCache::default())
+    }
+
+    // The regexes of the validators, to check against a scraper's limits
+    #[cfg(feature = "regex")]
+    pub(crate) fn regexes(&self) -> impl Iterator<Item = &regex::Regex> {
+        self.validators.values().filter_map(|validator| match validator {
+            Validator::Regex(regex) => Some(regex),
+            Validator::Fn(_) => None,
+        })
+    }
+
+    /// Like `check`, keeping the compiled selectors in `selectors`
+    pub(crate) fn compile(&self, selectors: &SelectorCache) -> Result<(), ConfigError> {
+        for selector in self.rules.iter().flat_map(ScrapeRule::selectors) {
+            selectors.get(&selector)?;
+        }
+        Ok(())
     }
 }
 
-impl Display for ScraperConfig {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", serde_json::to_string(self).unwrap())
+impl From<Vec<ScrapeRule>> for ScraperConfig {
+    fn from(rules: Vec<ScrapeRule>) -> Self {
+        ScraperConfig::new(rules)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct OutputConfig {
-    #[serde(rename = "type")]
-    output_type: String,
+impl Display for ScraperConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
 }
\ No newline at end of file
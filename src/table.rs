@@ -0,0 +1,16 @@
+use serde::Serialize;
+
+/// A table read by `HtmlScraper::scrape_typed_table`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// The cell of data row `row` (counting from 0) in the column titled `header`
+    pub fn get(&self, row: usize, header: &str) -> Option<&str> {
+        let column = self.headers.iter().position(|h| h == header)?;
+        self.rows.get(row)?.get(column).map(String::as_str)
+    }
+}
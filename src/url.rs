@@ -0,0 +1,52 @@
+// URL reference resolution on top of the `url` crate, which follows the
+// WHATWG URL standard browsers use
+
+use ::url::Url;
+
+// `localhost:8080/x` parses as a URL with the scheme `localhost`, but is a
+// host and port missing its scheme. Only a prefix that looks like a host
+// counts, so `tel:5551234` keeps its scheme.
+fn is_host_and_port(url: &str) -> bool {
+    let Some((host, rest)) = url.split_once(':') else {
+        return false;
+    };
+    if !host.eq_ignore_ascii_case("localhost") && !host.contains('.') {
+        return false;
+    }
+    let port = rest.bytes().take_while(u8::is_ascii_digit).count();
+    port > 0 && rest[port..].chars().next().is_none_or(|c| matches!(c, '/' | '?' | '#'))
+}
+
+fn parse(url: &str) -> Option<Url> {
+    let url = url.trim();
+    if is_host_and_port(url) {
+        return None;
+    }
+    Url::parse(url).ok()
+}
+
+/// The lowercased scheme of `url`, if it has one
+pub(crate) fn scheme(url: &str) -> Option<String> {
+    parse(url).map(|url| url.scheme().to_string())
+}
+
+/// Whether `url` carries a scheme, e.g. `https:` or `mailto:`
+pub(crate) fn is_absolute(url: &str) -> bool {
+    parse(url).is_some()
+}
+
+/// Resolves `reference` against the absolute `base` URL.
+/// Returns `None` if `base` isn't absolute.
+pub(crate) fn resolve(base: &str, reference: &str) -> Option<String> {
+    let reference = reference.trim();
+    if let Some(url) = parse(reference) {
+        return Some(url.into());
+    }
+    let base = parse(base)?;
+    let url = match is_host_and_port(reference) {
+        // Keeps the scheme of the base, like a `//`-relative reference
+        true => base.join(&format!("//{}", reference)),
+        false => base.join(reference),
+    };
+    url.ok().map(Into::into)
+}
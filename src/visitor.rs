@@ -1,7 +1,13 @@
-use scraper::{ElementRef, Selector};
-use std::{collections::HashMap};
+use scraper::{ElementRef, Node, Selector};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::{Arc, PoisonError, RwLock},
+};
 
-use crate::{cleaner::TextCleaner, scraper_config::ScrapeRule};
+use crate::{cleaner::TextCleaner, data_uri, scraper_config::{AttributeDecode, Coerce, ExtractKind, ScrapeRule}, url, ConfigError, ScrapeError};
 
 
 
@@ -12,12 +18,53 @@ pub trait Visitor {
         element: &ElementRef,
         rule: &ScrapeRule,
         cleaner: Option<&dyn TextCleaner>,
-    ) ->  HashMap<String, String>;
+    ) -> Result<HashMap<String, String>, ScrapeError>;
     fn visit_text(&mut self, text: &str, cleaner: Option<&dyn TextCleaner>) -> String;
 }
 
+/// A scraped field together with where it came from
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FieldResult {
+    pub value: String,
+    /// The selector of the rule that produced the field
+    pub selector: String,
+    /// Tag name of the (first) matched element, empty if nothing matched
+    pub source_tag: String,
+}
+
 // Updated concrete visitor
-pub struct ScraperVisitor;
+#[derive(Default)]
+pub struct ScraperVisitor {
+    pub(crate) base_url: Option<String>,
+    // Cleaners rules can pick by name
+    pub(crate) cleaners: HashMap<String, Arc<dyn TextCleaner>>,
+    // Only tracked when provenance was requested
+    pub(crate) sources: Option<HashMap<String, FieldResult>>,
+    // Include text nested in <script>, <style> and <template> elements
+    pub(crate) keep_script_style: bool,
+    pub(crate) normalize_attribute_names: bool,
+    // Attributes extracted as "true"/"false" depending on their presence
+    pub(crate) boolean_attributes: Vec<String>,
+    pub(crate) max_total_matches: Option<usize>,
+    // Re-escape collected text the way `inner_html` serializes it
+    pub(crate) raw_text: bool,
+    // Keep whitespace in <pre> and <code> from being cleaned away
+    pub(crate) preserve_preformatted: bool,
+    // Set by `scrape_tagged`: tagged rules only run with one of these tags
+    pub(crate) tags: Option<Vec<String>>,
+    // Elements materialized by `All` rules so far
+    pub(crate) total_matches: usize,
+    // Whether the rule visited last matched anything, for strict mode.
+    // Nested rules are visited first, so after a top-level rule this is its own.
+    pub(crate) matched: bool,
+    // Matches of `One` and `All` rules it rejects are skipped
+    pub(crate) element_filter: Option<ElementFilter>,
+    pub(crate) selectors: SelectorCache,
+}
+
+/// Decides whether a matched element is extracted, see
+/// `HtmlScraperBuilder::with_element_filter`
+pub type ElementFilter = Arc<dyn Fn(&ElementRef) -> bool + Send + Sync>;
 
 impl Visitor for ScraperVisitor {
     fn visit_element(
@@ -25,83 +72,628 @@ impl Visitor for ScraperVisitor {
         element: &ElementRef,
         rule: &ScrapeRule,
         cleaner: Option<&dyn TextCleaner>,
-    ) -> HashMap<String, String> {
+    ) -> Result<HashMap<String, String>, ScrapeError> {
         let mut result = HashMap::new();
-        match rule {
+        if let Some(tags) = &self.tags {
+            if !rule.tags().is_empty() && !rule.tags().iter().any(|tag| tags.contains(tag)) {
+                self.matched = true;
+                return Ok(result);
+            }
+        }
+        let named_cleaner = match rule.cleaner() {
+            Some(name) => Some(
+                self.cleaners
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| ScrapeError::UnknownCleaner(name.to_string()))?,
+            ),
+            None => None,
+        };
+        let cleaner = named_cleaner.as_deref().or(cleaner);
+        let matched = match rule {
             ScrapeRule::One {
                 selector,
                 name,
                 sub_rules,
                 attribute,
+                extract,
+                contains,
+                where_attr,
+                attribute_from,
+                decode,
+                coerce,
+                require_absolute_url,
+                resolve_url,
+                with_length,
+                default,
+                ..
             } => {
-                let selector = Selector::parse(selector).unwrap();
-                if let Some(selected_element) = element.select(&selector).next() {
+                let selector = self.selectors.get(selector)?;
+                let attribute_from = attribute_from.as_deref().map(|s| self.selectors.get(s)).transpose()?;
+                let attribute = attribute.as_ref().or(extract.attribute());
+                let selected_element =
+                    self.matches(element, &selector).find(|el| self.passes_filters(el, contains.as_deref(), where_attr.as_ref()));
+                if let Some(selected_element) = selected_element {
                     if let Some(sub_rules) = sub_rules {
                         for sub_rule in sub_rules {
-                            result.extend(self.visit_element(&selected_element, sub_rule, cleaner));
+                            result.extend(self.visit_element(&selected_element, sub_rule, cleaner)?);
                         }
                     } else if let Some(attr) = attribute {
-                        let value = selected_element
-                            .value()
-                            .attr(attr)
-                            .unwrap_or("")
-                            .to_string();
-                        result.insert(name.clone(), self.visit_text(&value, cleaner));
+                        if let Some(source) = attribute_source(selected_element, attribute_from.as_ref()) {
+                            let value = self.visit_attribute(
+                                &source,
+                                name,
+                                attr,
+                                *require_absolute_url,
+                                *decode,
+                                cleaner,
+                            )?;
+                            let value = self.resolve_url(value, *resolve_url)?;
+                            let value = coerced(name, coerce.as_ref(), value)?;
+                            result.insert(name.clone(), length_value(value, *with_length));
+                            self.record(rule, Some(&source));
+                        }
                     } else {
-                        let text = selected_element.text().collect::<String>();
-                        result.insert(name.clone(), self.visit_text(&text, cleaner));
+                        let value = self.extract_content(&selected_element, extract, cleaner);
+                        let value = coerced(name, coerce.as_ref(), value)?;
+                        result.insert(name.clone(), length_value(value, *with_length));
+                        self.record(rule, Some(&selected_element));
                     }
+                } else if let Some(default) = default {
+                    let value = self.visit_text(default, cleaner);
+                    let value = coerced(name, coerce.as_ref(), value)?;
+                    result.insert(name.clone(), length_value(value, *with_length));
+                    self.record(rule, None);
                 }
+                selected_element.is_some()
             }
             ScrapeRule::All {
                 selector,
                 name,
                 sub_rules,
                 attribute,
+                extract,
+                contains,
+                where_attr,
+                attribute_from,
+                decode,
+                coerce,
+                require_absolute_url,
+                resolve_url,
+                with_length,
+                with_section,
+                offset,
+                limit,
+                dedup,
+                default,
+                ..
             } => {
-                let selector = Selector::parse(selector).unwrap();
-                let selected_elements: Vec<ElementRef> = element.select(&selector).collect();
+                let selector = self.selectors.get(selector)?;
+                let attribute_from = attribute_from.as_deref().map(|s| self.selectors.get(s)).transpose()?;
+                let attribute = attribute.as_ref().or(extract.attribute());
+                let selected_elements: Vec<ElementRef> = self.matches(element, &selector)
+                    .filter(|el| self.passes_filters(el, contains.as_deref(), where_attr.as_ref()))
+                    .skip(offset.unwrap_or(0))
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect();
+                self.count_matches(selected_elements.len())?;
+                let sections = if *with_section {
+                    self.preceding_headings(&selected_elements, cleaner)
+                } else {
+                    vec![None; selected_elements.len()]
+                };
 
-                let values: Vec<String> = selected_elements
+                let values: Vec<(String, Option<String>)> = selected_elements
                     .iter()
-                    .map(|selected_element| {
+                    .zip(sections)
+                    .filter_map(|(selected_element, section)| {
                         if let Some(sub_rules) = sub_rules {
                             let mut sub_result = HashMap::new();
                             for sub_rule in sub_rules {
-                                sub_result.extend(self.visit_element(
-                                    &selected_element,
-                                    sub_rule,
-                                    cleaner,
-                                ));
+                                match self.visit_element(selected_element, sub_rule, cleaner) {
+                                    Ok(fields) => sub_result.extend(fields),
+                                    Err(e) => return Some(Err(e)),
+                                }
+                            }
+                            let mut object = ordered_fields(sub_rules, sub_result);
+                            if let Some(section) = section {
+                                object.insert("section".to_string(), Value::String(section));
                             }
-                            serde_json::to_string(&sub_result).unwrap()
+                            Some(Ok((serde_json::to_string(&object).unwrap(), None)))
                         } else if let Some(attr) = attribute {
-                            let value = selected_element
-                                .value()
-                                .attr(attr)
-                                .unwrap_or("")
-                                .to_string();
-                            self.visit_text(&value, cleaner)
+                            // Matches without an `attribute_from` element are left out
+                            let source = attribute_source(*selected_element, attribute_from.as_ref())?;
+                            let value = self.visit_attribute(
+                                &source,
+                                name,
+                                attr,
+                                *require_absolute_url,
+                                *decode,
+                                cleaner,
+                            )
+                            .and_then(|value| self.resolve_url(value, *resolve_url));
+                            Some(value.map(|value| (value, section)))
                         } else {
-                            self.visit_text(&selected_element.text().collect::<String>(), cleaner)
+                            Some(Ok((self.extract_content(selected_element, extract, cleaner), section)))
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+                let values = match default {
+                    Some(default) if selected_elements.is_empty() => vec![(self.visit_text(default, cleaner), None)],
+                    _ => values,
+                };
+                let values = match coerce {
+                    Some(coerce) if sub_rules.is_none() => values
+                        .into_iter()
+                        .map(|(value, section)| Ok((coerced(name, Some(coerce), value)?, section)))
+                        .collect::<Result<_, ScrapeError>>()?,
+                    _ => values,
+                };
+                let values = if *dedup {
+                    let mut seen = HashSet::new();
+                    values.into_iter().filter(|(value, _)| seen.insert(value.clone())).collect()
+                } else {
+                    values
+                };
+
+                let values = if (*with_length || *with_section) && sub_rules.is_none() {
+                    let values: Vec<Value> = values
+                        .iter()
+                        .map(|(value, section)| {
+                            let mut object = if *with_length {
+                                with_length_object(value)
+                            } else {
+                                serde_json::json!({ "value": value })
+                            };
+                            if let Some(section) = section {
+                                object["section"] = Value::String(section.clone());
+                            }
+                            object
+                        })
+                        .collect();
+                    serde_json::to_string(&values).unwrap()
+                } else {
+                    let values: Vec<&String> = values.iter().map(|(value, _)| value).collect();
+                    serde_json::to_string(&values).unwrap()
+                };
+                result.insert(name.clone(), values);
+                self.record(rule, selected_elements.first());
+                !selected_elements.is_empty()
+            }
+            ScrapeRule::Text {
+                selector,
+                name,
+                paragraph_separator,
+                separator,
+                skip,
+                default,
+                coerce,
+                ..
+            } => {
+                let selector = self.selectors.get(selector)?;
+                let selected_elements: Vec<ElementRef> = self.matches(element, &selector).skip(*skip).collect();
+                let text = if let Some(default) = default.as_ref().filter(|_| selected_elements.is_empty()) {
+                    self.visit_text(default, cleaner)
+                } else if let Some(separator) = paragraph_separator {
+                    selected_elements
+                        .iter()
+                        .map(|el| self.element_text(el, cleaner))
+                        .filter(|text| !text.trim().is_empty())
+                        .collect::<Vec<String>>()
+                        .join(separator)
+                } else if let Some(separator) = separator {
+                    selected_elements
+                        .iter()
+                        .map(|el| self.element_text(el, cleaner))
+                        .collect::<Vec<String>>()
+                        .join(separator)
+                } else if self.preserve_preformatted {
+                    selected_elements
+                        .iter()
+                        .map(|el| self.element_text(el, cleaner))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                } else {
+                    let text = selected_elements
+                        .iter()
+                        .map(|el| self.collect_text(el))
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    self.visit_text(&text, cleaner)
+                };
+
+                // An empty field for nothing matching isn't a malformed value
+                let text = if selected_elements.is_empty() && default.is_none() {
+                    text
+                } else {
+                    coerced(name, coerce.as_ref(), text)?
+                };
+                result.insert(name.clone(), text);
+                self.record(rule, selected_elements.first());
+                !selected_elements.is_empty()
+            }
+            ScrapeRule::Microdata {
+                scope_selector,
+                name,
+                ..
+            } => {
+                let selector = self.selectors.get(scope_selector)?;
+                let scope = self.matches(element, &selector).next();
+                if let Some(scope) = scope {
+                    let item = self.visit_item(&scope, cleaner);
+                    result.insert(name.clone(), serde_json::to_string(&item).unwrap());
+                    self.record(rule, Some(&scope));
+                }
+                scope.is_some()
+            }
+            ScrapeRule::InputValue { selector, name, .. } => {
+                let selector = self.selectors.get(selector)?;
+                let control = self.matches(element, &selector).next();
+                if let Some(control) = control {
+                    let value = self.visit_input_value(&control);
+                    result.insert(name.clone(), self.visit_text(&value, cleaner));
+                    self.record(rule, Some(&control));
+                }
+                control.is_some()
+            }
+            ScrapeRule::Count { selector, name, .. } => {
+                let selector = self.selectors.get(selector)?;
+                let matches: Vec<ElementRef> = self.matches(element, &selector).collect();
+                result.insert(name.clone(), matches.len().to_string());
+                self.record(rule, matches.first());
+                !matches.is_empty()
+            }
+            ScrapeRule::Exists { selector, name, .. } => {
+                let selector = self.selectors.get(selector)?;
+                let found = self.matches(element, &selector).next();
+                result.insert(name.clone(), found.is_some().to_string());
+                self.record(rule, found.as_ref());
+                found.is_some()
+            }
+            ScrapeRule::ListTree { selector, name, .. } => {
+                let selector = self.selectors.get(selector)?;
+                let list = self.matches(element, &selector).next();
+                if let Some(list) = list {
+                    let items = self.list_tree(&list, cleaner);
+                    result.insert(name.clone(), serde_json::to_string(&items).unwrap());
+                    self.record(rule, Some(&list));
+                }
+                list.is_some()
+            }
+            ScrapeRule::Attributes {
+                selector,
+                name,
+                attributes,
+                skip_missing,
+                ..
+            } => {
+                let selector = self.selectors.get(selector)?;
+                let selected_element = self.matches(element, &selector).next();
+                if let Some(selected_element) = selected_element {
+                    let mut values = Map::new();
+                    for attr in attributes {
+                        if *skip_missing && self.attr(&selected_element, attr).is_none() {
+                            continue;
                         }
+                        let value = self.visit_attribute(&selected_element, name, attr, false, None, cleaner)?;
+                        values.insert(attr.clone(), Value::String(value));
+                    }
+                    result.insert(name.clone(), Value::Object(values).to_string());
+                    self.record(rule, Some(&selected_element));
+                }
+                selected_element.is_some()
+            }
+            ScrapeRule::Links { selector, name, .. } => {
+                let selector = self.selectors.get(selector)?;
+                let selected_elements: Vec<ElementRef> = self.matches(element, &selector).collect();
+                self.count_matches(selected_elements.len())?;
+
+                let links: Vec<(ElementRef, Value)> = selected_elements
+                    .into_iter()
+                    .filter_map(|selected_element| {
+                        let href = self.attr(&selected_element, "href")?.trim().to_string();
+                        let text = self.element_text(&selected_element, cleaner);
+                        Some((selected_element, serde_json::json!({ "text": text, "href": href })))
                     })
                     .collect();
 
+                let values: Vec<&Value> = links.iter().map(|(_, link)| link).collect();
+                result.insert(name.clone(), serde_json::to_string(&values).unwrap());
+                self.record(rule, links.first().map(|(source, _)| source));
+                !links.is_empty()
+            }
+            ScrapeRule::Images {
+                selector,
+                name,
+                resolve_url,
+                ..
+            } => {
+                let selector = self.selectors.get(selector)?;
+                let selected_elements: Vec<ElementRef> = self.matches(element, &selector).collect();
+                self.count_matches(selected_elements.len())?;
+
+                let mut images = Vec::new();
+                for selected_element in selected_elements {
+                    let Some(src) = self.attr(&selected_element, "src") else {
+                        continue;
+                    };
+                    let src = self.resolve_url(src.trim().to_string(), *resolve_url)?;
+                    let alt = self.attr(&selected_element, "alt").unwrap_or("").to_string();
+                    let alt = self.visit_text(&alt, cleaner);
+                    images.push((selected_element, serde_json::json!({ "src": src, "alt": alt })));
+                }
+
+                let values: Vec<&Value> = images.iter().map(|(_, image)| image).collect();
+                result.insert(name.clone(), serde_json::to_string(&values).unwrap());
+                self.record(rule, images.first().map(|(source, _)| source));
+                !images.is_empty()
+            }
+            ScrapeRule::Table {
+                selector, name, header, ..
+            } => {
+                let selector = self.selectors.get(selector)?;
+                let table = self.matches(element, &selector).next();
+                if let Some(table) = table {
+                    let mut rows: Vec<Vec<String>> =
+                        self.table_rows(&table, cleaner).into_iter().map(|(values, _)| values).collect();
+                    let headers = if *header && !rows.is_empty() { Some(rows.remove(0)) } else { None };
+                    let width = match &headers {
+                        Some(headers) => headers.len(),
+                        None => rows.iter().map(Vec::len).max().unwrap_or(0),
+                    };
+
+                    let values: Vec<Value> = rows
+                        .into_iter()
+                        .map(|mut row| {
+                            row.resize(width, String::new());
+                            match &headers {
+                                Some(headers) => Value::Object(
+                                    headers.iter().cloned().zip(row.into_iter().map(Value::String)).collect(),
+                                ),
+                                None => Value::from(row),
+                            }
+                        })
+                        .collect();
+                    result.insert(name.clone(), Value::Array(values).to_string());
+                    self.record(rule, Some(&table));
+                }
+                table.is_some()
+            }
+            ScrapeRule::NextSiblingText { selector, name, .. } => {
+                let selector = self.selectors.get(selector)?;
+                let selected_elements: Vec<ElementRef> = self.matches(element, &selector).collect();
+
+                let values: Vec<String> = selected_elements
+                    .iter()
+                    .map(|selected_element| {
+                        match selected_element.next_siblings().find_map(ElementRef::wrap) {
+                            Some(sibling) => self.element_text(&sibling, cleaner),
+                            None => self.visit_text("", cleaner),
+                        }
+                    })
+                    .collect();
+
+                result.insert(name.clone(), serde_json::to_string(&values).unwrap());
+                self.record(rule, selected_elements.first());
+                !selected_elements.is_empty()
+            }
+            ScrapeRule::Repeat {
+                selector_template,
+                name,
+                count,
+                sub_rules,
+                ..
+            } => {
+                let mut values = Vec::new();
+                let mut first = None;
+                for i in 1..=*count {
+                    let selector = self.selectors.get(&selector_template.replace("{i}", &i.to_string()))?;
+                    let Some(selected_element) = self.matches(element, &selector).next() else {
+                        continue;
+                    };
+                    first.get_or_insert(selected_element);
+                    if let Some(sub_rules) = sub_rules {
+                        let mut sub_result = HashMap::new();
+                        for sub_rule in sub_rules {
+                            sub_result.extend(self.visit_element(&selected_element, sub_rule, cleaner)?);
+                        }
+                        values.push(serde_json::to_string(&ordered_fields(sub_rules, sub_result)).unwrap());
+                    } else {
+                        values.push(self.element_text(&selected_element, cleaner));
+                    }
+                }
+
+                result.insert(name.clone(), serde_json::to_string(&values).unwrap());
+                self.record(rule, first.as_ref());
+                first.is_some()
+            }
+            ScrapeRule::ClosestAncestor {
+                selector,
+                ancestor_selector,
+                name,
+                attribute,
+                ..
+            } => {
+                let selector = self.selectors.get(selector)?;
+                let ancestor_selector = self.selectors.get(ancestor_selector)?;
+                let selected_elements: Vec<ElementRef> = self.matches(element, &selector).collect();
+
+                let values: Vec<String> = selected_elements
+                    .iter()
+                    .map(|selected_element| {
+                        let ancestor = selected_element
+                            .ancestors()
+                            .filter_map(ElementRef::wrap)
+                            .find(|ancestor| ancestor_selector.matches(ancestor));
+                        match (ancestor, attribute) {
+                            (Some(ancestor), Some(attr)) => {
+                                self.visit_attribute(&ancestor, name, attr, false, None, cleaner)
+                            }
+                            (Some(ancestor), None) => {
+                                Ok(self.element_text(&ancestor, cleaner))
+                            }
+                            (None, _) => Ok(String::new()),
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+
                 result.insert(name.clone(), serde_json::to_string(&values).unwrap());
+                self.record(rule, selected_elements.first());
+                !selected_elements.is_empty()
+            }
+            ScrapeRule::OwnText {
+                selector,
+                name,
+                index,
+                ..
+            } => {
+                let selector = self.selectors.get(selector)?;
+                let selected_element = self.matches(element, &selector).next();
+                if let Some(selected_element) = selected_element {
+                    if let Some(text) = self.own_text(&selected_element, *index) {
+                        result.insert(name.clone(), self.visit_text(&text, cleaner));
+                        self.record(rule, Some(&selected_element));
+                    }
+                }
+                selected_element.is_some()
+            }
+            ScrapeRule::DynamicKey {
+                selector,
+                key_attribute,
+                value_attribute,
+                name,
+                ..
+            } => {
+                let selector = self.selectors.get(selector)?;
+                let selected_elements: Vec<ElementRef> = self.matches(element, &selector).collect();
+
+                let mut object = Map::new();
+                for selected_element in &selected_elements {
+                    let key = self.attr(selected_element, key_attribute);
+                    let value = self.attr(selected_element, value_attribute);
+                    if let (Some(key), Some(value)) = (key, value) {
+                        if !object.contains_key(key) {
+                            let value = self.visit_text(value, cleaner);
+                            object.insert(key.to_string(), Value::String(value));
+                        }
+                    }
+                }
+
+                result.insert(name.clone(), serde_json::to_string(&object).unwrap());
+                self.record(rule, selected_elements.first());
+                !selected_elements.is_empty()
+            }
+            ScrapeRule::Coalesce { name, candidates, .. } => {
+                for candidate in candidates {
+                    let value = self
+                        .visit_element(element, candidate, cleaner)?
+                        .remove(candidate.output_name());
+                    // A candidate that matched nothing still yields a value,
+                    // e.g. "false" from `Exists` or "0" from `Count`
+                    if let Some(value) = value.filter(|v| self.matched && !v.trim().is_empty()) {
+                        result.insert(name.clone(), value);
+                        if let Some(sources) = &mut self.sources {
+                            if let Some(source) = sources.get(candidate.output_name()).cloned() {
+                                sources.insert(name.clone(), source);
+                            }
+                        }
+                        break;
+                    }
+                }
+                result.contains_key(name)
             }
-            ScrapeRule::Text { selector, name } => {
-                let selector = Selector::parse(selector).unwrap();
-                let text: String = element
-                    .select(&selector)
-                    .map(|el| el.text().collect::<String>())
-                    .collect::<Vec<String>>()
-                    .join(" ");
+            ScrapeRule::OneOf {
+                selectors,
+                name,
+                attribute,
+                ..
+            } => {
+                let mut found = None;
+                for selector in selectors {
+                    let compiled = self.selectors.get(selector)?;
+                    let selected_element = self.matches(element, &compiled).next();
+                    if let Some(selected_element) = selected_element {
+                        found = Some((selector, selected_element));
+                        break;
+                    }
+                }
 
-                result.insert(name.clone(), self.visit_text(&text, cleaner));
+                if let Some((selector, selected_element)) = found {
+                    let value = match attribute {
+                        Some(attr) => self.visit_attribute(&selected_element, name, attr, false, None, cleaner)?,
+                        None => self.element_text(&selected_element, cleaner),
+                    };
+                    result.insert(name.clone(), value);
+                    self.record_selector(name, selector, Some(&selected_element));
+                }
+                found.is_some()
             }
+            ScrapeRule::Resolve {
+                name,
+                steps,
+                default,
+                ..
+            } => {
+                let mut resolved = None;
+                for step in steps {
+                    let selector = self.selectors.get(&step.selector)?;
+                    let Some(selected_element) = self.matches(element, &selector).next() else {
+                        continue;
+                    };
+                    let value = match &step.attribute {
+                        Some(attr) if self.attr(&selected_element, attr).is_none() => continue,
+                        Some(attr) => self.visit_attribute(&selected_element, name, attr, false, None, cleaner)?,
+                        None => self.element_text(&selected_element, cleaner),
+                    };
+                    if !value.trim().is_empty() {
+                        resolved = Some((value, step, selected_element));
+                        break;
+                    }
+                }
+
+                match resolved {
+                    Some((value, step, selected_element)) => {
+                        result.insert(name.clone(), value);
+                        self.record_selector(name, &step.selector, Some(&selected_element));
+                        true
+                    }
+                    None => {
+                        if let Some(default) = default {
+                            result.insert(name.clone(), self.visit_text(default, cleaner));
+                        }
+                        false
+                    }
+                }
+            }
+            // `rule` checks whether it's required itself
+            ScrapeRule::Optional { rule } => {
+                match self.visit_element(element, rule, cleaner) {
+                    Ok(fields) => result.extend(fields),
+                    Err(
+                        ScrapeError::RelativeUrl { .. }
+                        | ScrapeError::InvalidDataUri(_)
+                        | ScrapeError::RequiredFieldMissing(_),
+                    ) => {}
+                    Err(err) => return Err(err),
+                }
+                true
+            }
+        };
+        self.matched = matched;
+        if rule.required() && !matched {
+            return Err(ScrapeError::RequiredFieldMissing(rule.name().to_string()));
         }
-        result
+        if rule.output_name() != rule.name() {
+            if let Some(value) = result.remove(rule.name()) {
+                result.insert(rule.output_name().to_string(), value);
+            }
+            if let Some(sources) = &mut self.sources {
+                if let Some(source) = sources.remove(rule.name()) {
+                    sources.insert(rule.output_name().to_string(), source);
+                }
+            }
+        }
+        Ok(result)
     }
 
     fn visit_text(&mut self, text: &str, cleaner: Option<&dyn TextCleaner>) -> String {
@@ -113,3 +705,748 @@ impl Visitor for ScraperVisitor {
     }
 }
 
+impl ScraperVisitor {
+    pub(crate) fn accepts(&self, element: &ElementRef) -> bool {
+        self.element_filter.as_ref().is_none_or(|filter| filter(element))
+    }
+
+    // The matches of `selector` the element filter accepts, which are all
+    // a rule gets to see
+    fn matches<'a, 'b>(&'b self, element: &ElementRef<'a>, selector: &'b Selector) -> impl Iterator<Item = ElementRef<'a>> + 'b
+    where
+        'a: 'b,
+    {
+        select(element, selector).filter(|el| self.accepts(el))
+    }
+
+    // See `contains` and `where_attr` on `One` and `All`
+    fn passes_filters(&self, element: &ElementRef, contains: Option<&str>, where_attr: Option<&(String, String)>) -> bool {
+        where_attr.is_none_or(|(name, value)| self.attr(element, name) == Some(value.as_str()))
+            && contains.is_none_or(|needle| self.collect_text(element).to_lowercase().contains(&needle.to_lowercase()))
+    }
+
+    fn count_matches(&mut self, matches: usize) -> Result<(), ScrapeError> {
+        self.total_matches += matches;
+        match self.max_total_matches {
+            Some(limit) if self.total_matches > limit => Err(ScrapeError::MatchLimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    fn attr<'a>(&self, element: &ElementRef<'a>, name: &str) -> Option<&'a str> {
+        if self.normalize_attribute_names {
+            element
+                .value()
+                .attrs()
+                .find(|(attr, _)| attr.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+        } else {
+            element.value().attr(name)
+        }
+    }
+
+    // The cleaned text of an element. With `preserve_preformatted`, text in
+    // <pre> and <code> elements is kept verbatim and only the text around
+    // it is cleaned.
+    fn element_text(&mut self, element: &ElementRef, cleaner: Option<&dyn TextCleaner>) -> String {
+        if !self.preserve_preformatted || cleaner.is_none() {
+            let text = self.collect_text(element);
+            return self.visit_text(&text, cleaner);
+        }
+        let in_preformatted = element
+            .ancestors()
+            .chain(std::iter::once(**element))
+            .filter_map(ElementRef::wrap)
+            .any(|el| is_preformatted(el.value().name()));
+        if in_preformatted {
+            return self.collect_text(element);
+        }
+
+        let mut segments = vec![(String::new(), None)];
+        self.collect_segments(element, &mut segments);
+        let mut text = String::new();
+        let mut previous_separator = None;
+        for (segment, separator) in segments {
+            let segment = match separator {
+                Some(_) => segment,
+                None => self.visit_text(&segment, cleaner),
+            };
+            if segment.trim().is_empty() {
+                continue;
+            }
+            if !text.is_empty() {
+                text.push(separator.or(previous_separator).unwrap_or(' '));
+            }
+            text.push_str(&segment);
+            previous_separator = separator;
+        }
+        text
+    }
+
+    // Splits the text of an element into runs to clean and verbatim runs,
+    // the latter with the separator that sets them off
+    fn collect_segments(&self, element: &ElementRef, segments: &mut Vec<(String, Option<char>)>) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(t) => {
+                    let (text, _) = segments.last_mut().unwrap();
+                    if self.raw_text {
+                        escape_text(t, text);
+                    } else {
+                        text.push_str(t);
+                    }
+                }
+                Node::Element(el)
+                    if !self.keep_script_style
+                        && matches!(el.name(), "script" | "style" | "template") => {}
+                Node::Element(el) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        if is_preformatted(el.name()) {
+                            let separator = if el.name() == "pre" { '\n' } else { ' ' };
+                            segments.push((self.collect_text(&child), Some(separator)));
+                            segments.push((String::new(), None));
+                        } else {
+                            self.collect_segments(&child, segments);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The rows of `table`, also inside `<thead>`, `<tbody>` and `<tfoot>` but
+    // not those of nested tables, with whether all their cells are `<th>`. A
+    // cell spanning several columns is repeated for each of them. Cells are
+    // cleaned with `cleaner`, or else have their whitespace collapsed.
+    pub(crate) fn table_rows(&mut self, table: &ElementRef, cleaner: Option<&dyn TextCleaner>) -> Vec<(Vec<String>, bool)> {
+        let rows = table.child_elements().flat_map(|child| match child.value().name() {
+            "thead" | "tbody" | "tfoot" => child.child_elements().collect(),
+            _ => vec![child],
+        });
+        let mut result = Vec::new();
+        for row in rows.filter(|row| row.value().name() == "tr") {
+            let cells: Vec<ElementRef> = row
+                .child_elements()
+                .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                .collect();
+            let mut values = Vec::new();
+            for cell in &cells {
+                let text = self.collect_text(cell);
+                let text = match cleaner {
+                    Some(cleaner) => self.visit_text(&text, Some(cleaner)),
+                    None => text.split_whitespace().collect::<Vec<_>>().join(" "),
+                };
+                let span = cell.value().attr("colspan").and_then(|span| span.trim().parse().ok()).unwrap_or(1);
+                values.extend(std::iter::repeat_n(text, span.clamp(1, 1000)));
+            }
+            let is_header = !cells.is_empty() && cells.iter().all(|cell| cell.value().name() == "th");
+            result.push((values, is_header));
+        }
+        result
+    }
+
+    // Collects the text of an element and its descendants. Unless configured
+    // otherwise, text in nested <script>, <style> and <template> elements is
+    // left out; the element itself is always collected, so selecting a
+    // `script` still yields its contents.
+    pub(crate) fn collect_text(&self, element: &ElementRef) -> String {
+        if self.keep_script_style && !self.raw_text {
+            return element.text().collect();
+        }
+        let mut text = String::new();
+        let escape = self.raw_text && !is_raw_text_element(element.value().name());
+        self.collect_text_into(element, &mut text, escape);
+        text
+    }
+
+    fn collect_text_into(&self, element: &ElementRef, text: &mut String, escape: bool) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(t) if escape => escape_text(t, text),
+                Node::Text(t) => text.push_str(t),
+                Node::Element(el)
+                    if !self.keep_script_style
+                        && matches!(el.name(), "script" | "style" | "template") => {}
+                Node::Element(el) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        let escape = escape && !is_raw_text_element(el.name());
+                        self.collect_text_into(&child, text, escape);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The text or markup of a match for `extract`. Markup isn't cleaned, as
+    // cleaners are written for text.
+    fn extract_content(&mut self, element: &ElementRef, extract: &ExtractKind, cleaner: Option<&dyn TextCleaner>) -> String {
+        match extract {
+            ExtractKind::InnerHtml => element.inner_html(),
+            ExtractKind::OuterHtml => element.html(),
+            ExtractKind::Text | ExtractKind::Attribute(_) => self.element_text(element, cleaner),
+        }
+    }
+
+    // The items of a list, see `ScrapeRule::ListTree`. Lists directly inside
+    // another list, which browsers also render nested, follow the item
+    // before them like lists inside an item do.
+    fn list_tree(&mut self, list: &ElementRef, cleaner: Option<&dyn TextCleaner>) -> Vec<Value> {
+        let mut items = Vec::new();
+        for child in list.child_elements() {
+            match child.value().name() {
+                "li" => {
+                    let mut text = String::new();
+                    let mut nested = Vec::new();
+                    for node in child.children() {
+                        match node.value() {
+                            Node::Text(t) if self.raw_text => escape_text(t, &mut text),
+                            Node::Text(t) => text.push_str(t),
+                            Node::Element(el) if is_list(el.name()) => {
+                                if let Some(sublist) = ElementRef::wrap(node) {
+                                    nested.push(Value::Array(self.list_tree(&sublist, cleaner)));
+                                }
+                            }
+                            Node::Element(el)
+                                if !self.keep_script_style
+                                    && matches!(el.name(), "script" | "style" | "template") => {}
+                            Node::Element(_) => {
+                                if let Some(el) = ElementRef::wrap(node) {
+                                    text.push_str(&self.collect_text(&el));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    if !text.trim().is_empty() {
+                        items.push(Value::String(self.visit_text(&text, cleaner)));
+                    }
+                    items.extend(nested);
+                }
+                name if is_list(name) => items.push(Value::Array(self.list_tree(&child, cleaner))),
+                _ => {}
+            }
+        }
+        items
+    }
+
+    // The direct text children of an element, all of them or the `index`th
+    // one that isn't whitespace only
+    pub(crate) fn own_text(&self, element: &ElementRef, index: Option<usize>) -> Option<String> {
+        let texts = element.children().filter_map(|child| match child.value() {
+            Node::Text(t) => Some(&**t),
+            _ => None,
+        });
+        let texts: Vec<&str> = match index {
+            Some(index) => vec![texts.filter(|t| !t.trim().is_empty()).nth(index)?],
+            None => texts.collect(),
+        };
+
+        let mut text = String::new();
+        for t in texts {
+            if self.raw_text {
+                escape_text(t, &mut text);
+            } else {
+                text.push_str(t);
+            }
+        }
+        Some(text)
+    }
+
+    // The cleaned text of the closest heading before each of `elements`,
+    // found in one pass over the document in document order
+    fn preceding_headings(&mut self, elements: &[ElementRef], cleaner: Option<&dyn TextCleaner>) -> Vec<Option<String>> {
+        let Some(first) = elements.first() else {
+            return Vec::new();
+        };
+        let mut headings: Vec<Option<ElementRef>> = vec![None; elements.len()];
+        let positions: HashMap<_, usize> = elements.iter().enumerate().map(|(i, el)| (el.id(), i)).collect();
+        let root = first.ancestors().last().unwrap_or(**first);
+
+        let mut heading = None;
+        for node in root.descendants() {
+            if let Some(&i) = positions.get(&node.id()) {
+                headings[i] = heading;
+            }
+            if let Some(el) = ElementRef::wrap(node) {
+                if matches!(el.value().name(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6") {
+                    heading = Some(el);
+                }
+            }
+        }
+
+        headings
+            .into_iter()
+            .map(|heading| heading.map(|heading| self.element_text(&heading, cleaner)))
+            .collect()
+    }
+
+    fn record(&mut self, rule: &ScrapeRule, source: Option<&ElementRef>) {
+        let selector = match rule {
+            ScrapeRule::One { selector, .. }
+            | ScrapeRule::All { selector, .. }
+            | ScrapeRule::Text { selector, .. }
+            | ScrapeRule::InputValue { selector, .. }
+            | ScrapeRule::NextSiblingText { selector, .. }
+            | ScrapeRule::ClosestAncestor { selector, .. }
+            | ScrapeRule::OwnText { selector, .. }
+            | ScrapeRule::DynamicKey { selector, .. }
+            | ScrapeRule::Count { selector, .. }
+            | ScrapeRule::Exists { selector, .. }
+            | ScrapeRule::ListTree { selector, .. }
+            | ScrapeRule::Attributes { selector, .. }
+            | ScrapeRule::Links { selector, .. }
+            | ScrapeRule::Images { selector, .. }
+            | ScrapeRule::Table { selector, .. } => selector,
+            ScrapeRule::Microdata { scope_selector, .. } => scope_selector,
+            ScrapeRule::Repeat {
+                selector_template, ..
+            } => selector_template,
+            // Recorded by their candidates, steps or inner rule, or by the
+            // selector that matched
+            ScrapeRule::Coalesce { .. }
+            | ScrapeRule::Resolve { .. }
+            | ScrapeRule::OneOf { .. }
+            | ScrapeRule::Optional { .. } => return,
+        };
+        self.record_selector(rule.name(), selector, source);
+    }
+
+    fn record_selector(&mut self, name: &str, selector: &str, source: Option<&ElementRef>) {
+        let Some(sources) = &mut self.sources else {
+            return;
+        };
+        sources.insert(
+            name.to_string(),
+            FieldResult {
+                value: String::new(),
+                selector: selector.to_string(),
+                source_tag: source.map(|el| el.value().name().to_string()).unwrap_or_default(),
+            },
+        );
+    }
+
+    fn visit_attribute(
+        &mut self,
+        element: &ElementRef,
+        name: &str,
+        attr: &str,
+        require_absolute_url: bool,
+        decode: Option<AttributeDecode>,
+        cleaner: Option<&dyn TextCleaner>,
+    ) -> Result<String, ScrapeError> {
+        if self.boolean_attributes.iter().any(|a| a.eq_ignore_ascii_case(attr)) {
+            return Ok(self.attr(element, attr).is_some().to_string());
+        }
+        let value = self.attr(element, attr).unwrap_or("").to_string();
+        if let Some(AttributeDecode::DataUri) = decode {
+            let (mime, bytes) =
+                data_uri::parse(&value).ok_or_else(|| ScrapeError::InvalidDataUri(name.to_string()))?;
+            let decoded = serde_json::json!({ "mime": mime, "base64": data_uri::base64_encode(&bytes) });
+            return Ok(decoded.to_string());
+        }
+        let value = self.visit_text(&value, cleaner);
+        if require_absolute_url {
+            self.visit_url(name, value)
+        } else {
+            Ok(value)
+        }
+    }
+
+    // Makes sure a scraped URL is absolute, resolving it against the base URL if one is set
+    // See `resolve_url`. Empty values, e.g. of missing attributes, are kept
+    // instead of becoming the base URL.
+    fn resolve_url(&self, value: String, resolve: bool) -> Result<String, ScrapeError> {
+        match self.base_url.as_deref() {
+            Some(_) if !resolve || value.trim().is_empty() => Ok(value),
+            Some(base) if !url::is_absolute(base) => Err(ScrapeError::InvalidBaseUrl(base.to_string())),
+            Some(base) => Ok(url::resolve(base, &value).unwrap_or(value)),
+            None => Ok(value),
+        }
+    }
+
+    fn visit_url(&self, name: &str, value: String) -> Result<String, ScrapeError> {
+        if url::is_absolute(&value) {
+            return Ok(value);
+        }
+        if let Some(base) = self.base_url.as_deref().filter(|base| !url::is_absolute(base)) {
+            return Err(ScrapeError::InvalidBaseUrl(base.to_string()));
+        }
+        match self.base_url.as_deref().and_then(|base| url::resolve(base, &value)) {
+            Some(resolved) => Ok(resolved),
+            None => Err(ScrapeError::RelativeUrl {
+                name: name.to_string(),
+                value,
+            }),
+        }
+    }
+
+    // Collects the properties of an `itemscope` element. Properties of nested
+    // items belong to those items, so we don't descend past another `itemscope`.
+    fn visit_item(&mut self, scope: &ElementRef, cleaner: Option<&dyn TextCleaner>) -> Map<String, Value> {
+        let mut item = Map::new();
+        let mut pending: Vec<ElementRef> = scope.child_elements().collect();
+        pending.reverse();
+
+        while let Some(child) = pending.pop() {
+            let is_scope = child.value().attr("itemscope").is_some();
+            if let Some(props) = child.value().attr("itemprop") {
+                let value = if is_scope {
+                    Value::Object(self.visit_item(&child, cleaner))
+                } else {
+                    Value::String(self.visit_item_property(&child, cleaner))
+                };
+                for prop in props.split_whitespace() {
+                    match item.get_mut(prop) {
+                        Some(Value::Array(values)) => values.push(value.clone()),
+                        Some(existing) => *existing = Value::Array(vec![existing.take(), value.clone()]),
+                        None => {
+                            item.insert(prop.to_string(), value.clone());
+                        }
+                    }
+                }
+            }
+            if !is_scope {
+                let start = pending.len();
+                pending.extend(child.child_elements());
+                pending[start..].reverse();
+            }
+        }
+        item
+    }
+
+    // The value of a microdata property depends on the element it is declared on
+    fn visit_item_property(&mut self, element: &ElementRef, cleaner: Option<&dyn TextCleaner>) -> String {
+        let el = element.value();
+        let value = match el.name() {
+            "meta" => el.attr("content"),
+            "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => el.attr("src"),
+            "a" | "area" | "link" => el.attr("href"),
+            "object" => el.attr("data"),
+            "data" | "meter" => el.attr("value"),
+            "time" => el.attr("datetime"),
+            _ => None,
+        };
+        match value {
+            Some(value) => self.visit_text(value, cleaner),
+            None => self.element_text(element, cleaner),
+        }
+    }
+
+    // The current value of a form control, dispatched on its tag name
+    fn visit_input_value(&self, control: &ElementRef) -> String {
+        match control.value().name() {
+            "textarea" => control.text().collect(),
+            "select" => {
+                let options: Vec<ElementRef> = control
+                    .descendent_elements()
+                    .filter(|el| el.value().name() == "option")
+                    .collect();
+                // Without an explicit selection the browser shows the first option
+                options
+                    .iter()
+                    .find(|option| option.value().attr("selected").is_some())
+                    .or(options.first())
+                    .map(|option| match option.value().attr("value") {
+                        Some(value) => value.to_string(),
+                        None => option.text().collect(),
+                    })
+                    .unwrap_or_default()
+            }
+            _ => control.value().attr("value").unwrap_or("").to_string(),
+        }
+    }
+}
+
+/// Orders scraped fields like the rules that produced them are declared, so
+/// serialized results are stable
+pub(crate) fn ordered_fields(rules: &[ScrapeRule], mut fields: HashMap<String, String>) -> Map<String, Value> {
+    let mut ordered = Map::new();
+    for name in rules.iter().flat_map(ScrapeRule::field_names) {
+        if let Some(value) = fields.remove(name) {
+            ordered.insert(name.to_string(), Value::String(value));
+        }
+    }
+    let mut rest: Vec<_> = fields.into_iter().collect();
+    rest.sort();
+    ordered.extend(rest.into_iter().map(|(name, value)| (name, Value::String(value))));
+    ordered
+}
+
+/// Like `ordered_fields`, with the JSON that nested results are stored as
+/// decoded into the arrays and objects it encodes. `Text` and the like stay
+/// strings, even if they happen to hold JSON.
+pub(crate) fn structured_fields(rules: &[ScrapeRule], mut fields: HashMap<String, String>) -> Map<String, Value> {
+    let mut structured = Map::new();
+    for (name, rule) in rules.iter().flat_map(ScrapeRule::field_rules) {
+        if let Some(value) = fields.remove(name) {
+            let value = structured_value(rule, &value).unwrap_or(Value::String(value));
+            structured.insert(name.to_string(), value);
+        }
+    }
+    let mut rest: Vec<_> = fields.into_iter().collect();
+    rest.sort();
+    structured.extend(rest.into_iter().map(|(name, value)| (name, Value::String(value))));
+    structured
+}
+
+// Decodes a field the way `rule` stores it, `None` if it doesn't have that
+// shape
+fn structured_value(rule: &ScrapeRule, raw: &str) -> Option<Value> {
+    match rule {
+        ScrapeRule::One {
+            decode,
+            coerce,
+            with_length,
+            ..
+        } => {
+            let value = if *with_length {
+                serde_json::from_str(raw).ok()?
+            } else {
+                Value::String(raw.to_string())
+            };
+            coerced_value(decoded_value(value, decode.is_some())?, coerce.as_ref())
+        }
+        ScrapeRule::All {
+            sub_rules: Some(sub_rules),
+            ..
+        }
+        | ScrapeRule::Repeat {
+            sub_rules: Some(sub_rules),
+            ..
+        } => {
+            // The items are objects of sub-rule fields, except for a default
+            // standing in for them, which is kept as is
+            let items: Vec<String> = serde_json::from_str(raw).ok()?;
+            let items = items
+                .into_iter()
+                .map(|item| match serde_json::from_str(&item) {
+                    Ok(fields) => Value::Object(structured_fields(sub_rules, fields)),
+                    Err(_) => Value::String(item),
+                })
+                .collect();
+            Some(Value::Array(items))
+        }
+        ScrapeRule::All { decode, coerce, .. } => {
+            let items: Vec<Value> = serde_json::from_str(raw).ok()?;
+            items
+                .into_iter()
+                .map(|item| coerced_value(decoded_value(item, decode.is_some())?, coerce.as_ref()))
+                .collect::<Option<_>>()
+                .map(Value::Array)
+        }
+        ScrapeRule::Text {
+            coerce: Some(coerce), ..
+        } => coerce.parse(raw),
+        ScrapeRule::Text { .. }
+        | ScrapeRule::InputValue { .. }
+        | ScrapeRule::OwnText { .. }
+        | ScrapeRule::Resolve { .. }
+        | ScrapeRule::OneOf { .. } => {
+            Some(Value::String(raw.to_string()))
+        }
+        ScrapeRule::ListTree { .. } | ScrapeRule::Links { .. } | ScrapeRule::Images { .. } | ScrapeRule::Table { .. } => {
+            serde_json::from_str(raw).ok().map(Value::Array)
+        }
+        ScrapeRule::Microdata { .. } | ScrapeRule::DynamicKey { .. } | ScrapeRule::Attributes { .. } => {
+            serde_json::from_str(raw).ok().map(Value::Object)
+        }
+        ScrapeRule::NextSiblingText { .. } | ScrapeRule::Repeat { .. } | ScrapeRule::ClosestAncestor { .. } => {
+            serde_json::from_str::<Vec<String>>(raw).ok().map(Value::from)
+        }
+        // Which candidate won isn't stored, so prefer one that decodes the
+        // value over reading it as text
+        ScrapeRule::Coalesce { candidates, .. } => candidates
+            .iter()
+            .filter_map(|candidate| structured_value(candidate, raw))
+            .find(|value| !value.is_string()),
+        ScrapeRule::Count { .. } => raw.parse::<u64>().ok().map(Value::from),
+        ScrapeRule::Exists { .. } => raw.parse::<bool>().ok().map(Value::Bool),
+        ScrapeRule::Optional { rule } => structured_value(rule, raw),
+    }
+}
+
+// The JSON that `decode` stores in place of an attribute value, also when
+// wrapped by `with_length`
+fn decoded_value(value: Value, decoded: bool) -> Option<Value> {
+    match value {
+        Value::String(s) if decoded => serde_json::from_str(&s).ok(),
+        Value::String(s) => Some(Value::String(s)),
+        Value::Object(mut object) => {
+            if let Some(inner) = object.get_mut("value") {
+                *inner = decoded_value(inner.take(), decoded)?;
+            }
+            Some(Value::Object(object))
+        }
+        _ => None,
+    }
+}
+
+// Parses a `decoded_value` with `coerce`, also inside `with_length` objects
+fn coerced_value(value: Value, coerce: Option<&Coerce>) -> Option<Value> {
+    let Some(coerce) = coerce else {
+        return Some(value);
+    };
+    match value {
+        Value::String(s) => coerce.parse(&s),
+        Value::Object(mut object) => {
+            if let Some(inner) = object.get_mut("value") {
+                *inner = coerced_value(inner.take(), Some(coerce))?;
+            }
+            Some(Value::Object(object))
+        }
+        _ => None,
+    }
+}
+
+// Checks that `value` parses with `coerce` and normalizes it, e.g. trims
+// numbers and rewrites dates in ISO 8601
+fn coerced(name: &str, coerce: Option<&Coerce>, value: String) -> Result<String, ScrapeError> {
+    let Some(coerce) = coerce else {
+        return Ok(value);
+    };
+    match coerce.parse(&value) {
+        Some(Value::String(value)) => Ok(value),
+        Some(typed) => Ok(typed.to_string()),
+        None => Err(ScrapeError::CoercionFailed {
+            name: name.to_string(),
+            value,
+        }),
+    }
+}
+
+// A value with its length in characters, see `with_length`
+fn with_length_object(value: &str) -> Value {
+    serde_json::json!({ "value": value, "length": value.chars().count() })
+}
+
+fn length_value(value: String, with_length: bool) -> String {
+    if with_length {
+        with_length_object(&value).to_string()
+    } else {
+        value
+    }
+}
+
+// Matches of `selector` below `element`. Scraping starts from the `<html>`
+// element, which `ElementRef::select` leaves out, so at the top of the
+// document it is matched too, e.g. to read `<html lang>`.
+pub(crate) fn select<'a, 'b>(element: &ElementRef<'a>, selector: &'b Selector) -> impl Iterator<Item = ElementRef<'a>> + 'b
+where
+    'a: 'b,
+{
+    let is_document_element = element.parent().is_some_and(|parent| parent.value().is_document());
+    let root = (is_document_element && selector.matches(element)).then_some(*element);
+    root.into_iter().chain(element.select(selector))
+}
+
+// Selectors compiled so far, by their source. Clones share the cache, so
+// a scraper compiles each selector of its config once, not once per page.
+// Past `MAX_CACHED_SELECTORS`, e.g. with the generated selectors of `Repeat`
+// or ad-hoc ones per call, selectors are compiled without being kept.
+const MAX_CACHED_SELECTORS: usize = 1024;
+
+#[derive(Clone, Default)]
+pub(crate) struct SelectorCache(Arc<RwLock<HashMap<String, Selector>>>);
+
+impl SelectorCache {
+    /// Like `parse_selector`, reusing an earlier compilation of `selector`
+    pub(crate) fn get(&self, selector: &str) -> Result<Selector, ConfigError> {
+        let cached = self.0.read().unwrap_or_else(PoisonError::into_inner).get(selector).cloned();
+        if let Some(compiled) = cached {
+            return Ok(compiled);
+        }
+        let compiled = parse_selector(selector)?;
+        let mut cache = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        if cache.len() < MAX_CACHED_SELECTORS {
+            cache.insert(selector.to_string(), compiled.clone());
+        }
+        Ok(compiled)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+}
+
+/// Compiles a selector from a config, so a malformed one is reported instead
+/// of panicking
+pub(crate) fn parse_selector(selector: &str) -> Result<Selector, ConfigError> {
+    Selector::parse(&expand_heading_wildcard(selector))
+        .map_err(|_| ConfigError::InvalidSelector(selector.to_string()))
+}
+
+// Rewrites the `h*` shorthand, which isn't valid CSS, to match any heading
+// level. Quoted strings, e.g. in attribute selectors, are left alone.
+fn expand_heading_wildcard(selector: &str) -> Cow<'_, str> {
+    if !selector.contains("h*") {
+        return Cow::Borrowed(selector);
+    }
+
+    let mut expanded = String::with_capacity(selector.len());
+    let mut quote = None;
+    let mut previous = None;
+    let mut chars = selector.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, 'h' | 'H')
+                if chars.peek() == Some(&'*')
+                    && previous.is_none_or(|p: char| p.is_whitespace() || matches!(p, ',' | '>' | '+' | '~' | '(')) =>
+            {
+                chars.next();
+                expanded.push_str(":is(h1, h2, h3, h4, h5, h6)");
+                previous = Some('*');
+                continue;
+            }
+            _ => {}
+        }
+        expanded.push(c);
+        previous = Some(c);
+    }
+    Cow::Owned(expanded)
+}
+
+// The element an attribute is read from: the match itself, or the first of
+// it and its descendants matching the `attribute_from` selector
+fn attribute_source<'a>(element: ElementRef<'a>, attribute_from: Option<&Selector>) -> Option<ElementRef<'a>> {
+    let Some(selector) = attribute_from else {
+        return Some(element);
+    };
+    if selector.matches(&element) {
+        Some(element)
+    } else {
+        element.select(selector).next()
+    }
+}
+
+fn is_preformatted(name: &str) -> bool {
+    matches!(name, "pre" | "code")
+}
+
+fn is_list(name: &str) -> bool {
+    matches!(name, "ul" | "ol")
+}
+
+// Elements whose text the HTML serializer writes out unescaped
+fn is_raw_text_element(name: &str) -> bool {
+    matches!(
+        name,
+        "script" | "style" | "xmp" | "iframe" | "noembed" | "noframes" | "plaintext" | "noscript"
+    )
+}
+
+// Escapes text like html5ever's serializer does for `inner_html`
+fn escape_text(raw: &str, text: &mut String) {
+    for c in raw.chars() {
+        match c {
+            '&' => text.push_str("&amp;"),
+            '\u{a0}' => text.push_str("&nbsp;"),
+            '<' => text.push_str("&lt;"),
+            '>' => text.push_str("&gt;"),
+            c => text.push(c),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Rewrites XML, e.g. an RSS feed, into markup the HTML5 parser keeps intact
+
+// Elements the HTML parser never gives content
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "keygen", "link", "meta", "param",
+    "source", "track", "wbr",
+];
+
+fn escape(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            c => out.push(c),
+        }
+    }
+}
+
+// The end of the tag starting at the beginning of `s`, skipping `>` in quotes
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Converts `xml` so that, once parsed as HTML:
+/// - CDATA sections are text instead of comments
+/// - self-closing elements such as `<enclosure/>` don't swallow their
+///   following siblings
+/// - elements named like HTML void elements keep their content, by renaming
+///   them with an `x-` prefix, e.g. RSS `<link>` becomes `<x-link>`
+pub(crate) fn to_html(xml: &str) -> String {
+    let mut html = String::with_capacity(xml.len());
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        html.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").unwrap_or(cdata.len());
+            escape(&cdata[..end], &mut html);
+            rest = cdata.get(end + 3..).unwrap_or("");
+            continue;
+        }
+
+        let is_end_tag = rest.starts_with("</");
+        let name_start = if is_end_tag { 2 } else { 1 };
+        let name_len = rest[name_start..]
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+            .unwrap_or(rest.len() - name_start);
+        let end = match tag_end(rest) {
+            Some(end) if name_len > 0 => end,
+            // Comments, processing instructions and stray `<`s
+            _ => {
+                html.push('<');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        let name = &rest[name_start..name_start + name_len];
+        let attributes = &rest[name_start + name_len..end];
+        let (attributes, self_closing) = match attributes.strip_suffix('/') {
+            Some(attributes) => (attributes, true),
+            None => (attributes, false),
+        };
+        let name = if VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+            format!("x-{}", name)
+        } else {
+            name.to_string()
+        };
+
+        if is_end_tag {
+            html.push_str(&format!("</{}>", name));
+        } else if self_closing {
+            html.push_str(&format!("<{}{}></{}>", name, attributes, name));
+        } else {
+            html.push_str(&format!("<{}{}>", name, attributes));
+        }
+        rest = &rest[end + 1..];
+    }
+
+    html.push_str(rest);
+    html
+}
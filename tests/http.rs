@@ -0,0 +1,91 @@
+#![cfg(feature = "http")]
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+};
+
+use html_parser::{HtmlScraperBuilder, ScrapeConfig, ScrapeError, ScraperConfig};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Page {
+    title: String,
+}
+
+impl ScrapeConfig for Page {
+    fn get_config() -> ScraperConfig {
+        ScraperConfig::new(vec![])
+    }
+}
+
+impl From<HashMap<String, String>> for Page {
+    fn from(map: HashMap<String, String>) -> Self {
+        Page {
+            title: map.get("title").cloned().unwrap_or_default(),
+        }
+    }
+}
+
+const CONFIG: &str = r#"{ "rules": [{ "type": "One", "selector": "h1", "name": "title" }] }"#;
+
+// Serves a single request with `status` and `body`, sending the request's
+// headers back over the channel. Returns the server's URL.
+fn serve_once(status: &'static str, body: &'static str) -> (String, mpsc::Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let headers: Vec<String> = BufReader::new(&stream)
+            .lines()
+            .map(Result::unwrap)
+            .take_while(|line| !line.is_empty())
+            .collect();
+        sender.send(headers).unwrap();
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+        .unwrap();
+    });
+
+    (url, receiver)
+}
+
+#[tokio::test]
+async fn test_scrape_url() {
+    let (url, headers) = serve_once("200 OK", "<html><body><h1>Hello</h1></body></html>");
+    let scraper = HtmlScraperBuilder::new()
+        .with_config(CONFIG)
+        .with_user_agent("html_parser-test/1.0")
+        .build();
+
+    let page: Page = scraper.scrape_url(&url).await.unwrap();
+
+    assert_eq!(page.title, "Hello");
+    let headers = headers.recv().unwrap();
+    assert!(headers
+        .iter()
+        .any(|header| header.eq_ignore_ascii_case("user-agent: html_parser-test/1.0")));
+}
+
+#[tokio::test]
+async fn test_scrape_url_error_status() {
+    let (url, _headers) = serve_once("404 Not Found", "<h1>Not found</h1>");
+    let scraper = HtmlScraperBuilder::new().with_config(CONFIG).build();
+
+    let err = scraper.scrape_url::<Page>(&url).await.unwrap_err();
+
+    match err {
+        ScrapeError::Http(err) => assert_eq!(err.status().map(|status| status.as_u16()), Some(404)),
+        err => panic!("expected an HTTP error, got {:?}", err),
+    }
+}
@@ -1,16 +1,13 @@
 #[cfg(test)]
-
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, sync::Arc};
 
     use serde::{Deserialize, Serialize};
 
-    use html_parser::{DefaultCleaner, HtmlScraper, HtmlScraperBuilder, ScrapeConfig, ScrapeRule, ScraperConfig};
-
-    use super::*;
+    use html_parser::{to_csv, to_pretty_string, to_tsv, ChainCleaner, Coerce, ConfigError, DefaultCleaner, ElementExt, EntityDecodeCleaner, ExtractKind, ListMarkerCleaner, LowercaseCleaner, HtmlScraper, HtmlScraperBuilder, NumberCleaner, RuleOptions, RuleReport, ScrapeConfig, ScrapeError, ScrapeRule, ScraperConfig, Table, TextCleaner, UppercaseCleaner, Validator, HTML_BOOLEAN_ATTRIBUTES};
 
     // NewsArticle struct
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct NewsArticle {
     title: String,
     author: String,
@@ -20,28 +17,29 @@ pub struct NewsArticle {
 impl ScrapeConfig for NewsArticle {
     fn get_config() -> ScraperConfig {
         ScraperConfig::new(vec![
-                ScrapeRule::One {
-                    selector: "h1.title".to_string(),
-                    name: "title".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
-                ScrapeRule::One {
-                    selector: "div.author".to_string(),
-                    name: "author".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
-                ScrapeRule::All {
-                    selector: "div.paragraph".to_string(),
-                    name: "content".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
+                ScrapeRule::one("h1.title", "title"),
+                ScrapeRule::one("div.author", "author"),
+                ScrapeRule::all("div.paragraph", "content"),
             ])
     }
 }
 
+// Raw scrape output, for tests that only care about a few fields
+#[derive(Debug, Default, Deserialize)]
+pub struct Fields(HashMap<String, String>);
+
+impl ScrapeConfig for Fields {
+    fn get_config() -> ScraperConfig {
+        ScraperConfig::new(vec![])
+    }
+}
+
+impl From<HashMap<String, String>> for Fields {
+    fn from(map: HashMap<String, String>) -> Self {
+        Fields(map)
+    }
+}
+
 impl From<HashMap<String, String>> for NewsArticle {
     fn from(map: HashMap<String, String>) -> Self {
         NewsArticle {
@@ -55,16 +53,6 @@ impl From<HashMap<String, String>> for NewsArticle {
     }
 }
 
-impl Default for NewsArticle {
-    fn default() -> Self {
-        NewsArticle {
-            title: String::new(),
-            author: String::new(),
-            content: Vec::new(),
-        }
-    }
-}
-
     #[test]
     fn test_default() {
         let html = r#"
@@ -161,43 +149,13 @@ impl Default for NewsArticle {
         impl ScrapeConfig for Research {
             fn get_config() -> ScraperConfig {
                 ScraperConfig::new(vec![
-                        ScrapeRule::One {
-                            selector: "div.hlFld-Abstract".to_string(),
-                            name: "abstract_outer".to_string(),
-                            sub_rules: Some(vec![ScrapeRule::One {
-                                selector: "p.last".to_string(),
-                                name: "abstract_".to_string(),
-                                sub_rules: None,
-                                attribute: None,
-                            }]),
-                            attribute: None,
-                        },
-                        ScrapeRule::All {
-                            selector: ".abstractKeywords li a".to_string(),
-                            name: "keywords".to_string(),
-                            sub_rules: None,
-                            attribute: None,
-                        },
-                        ScrapeRule::One {
-                            selector: ".NLM_sec_level_1".to_string(),
-                            name: "intro_container".to_string(),
-                            sub_rules: Some(vec![ScrapeRule::Text {
-                                selector: "p".to_string(),
-                                name: "introduction".to_string(),
-                            }]),
-                            attribute: None,
-                        },
-                        ScrapeRule::All {
-                            selector: ".NLM_sec_level_2".to_string(),
-                            name: "sub_headings".to_string(),
-                            sub_rules: Some(vec![ScrapeRule::One {
-                                selector: "h3".to_string(),
-                                name: "heading".to_string(),
-                                sub_rules: None,
-                                attribute: None,
-                            }]),
-                            attribute: None,
-                        },
+                        ScrapeRule::one("div.hlFld-Abstract", "abstract_outer")
+                            .sub_rules(vec![ScrapeRule::one("p.last", "abstract_")]),
+                        ScrapeRule::all(".abstractKeywords li a", "keywords"),
+                        ScrapeRule::one(".NLM_sec_level_1", "intro_container")
+                            .sub_rules(vec![ScrapeRule::text("p", "introduction")]),
+                        ScrapeRule::all(".NLM_sec_level_2", "sub_headings")
+                            .sub_rules(vec![ScrapeRule::one("h3", "heading")]),
                     ]
                 )
             }
@@ -264,18 +222,9 @@ impl Default for NewsArticle {
 
         impl ScrapeConfig for News {
             fn get_config() -> ScraperConfig {
-                ScraperConfig::new(vec![ScrapeRule::One {
-                        selector: ".td-post-content".to_string(),
-                        name: "content".to_string(),
-                        sub_rules: Some(vec![ScrapeRule::All {
-                            selector: "p".to_string(),
-                            name: "paragraph".to_string(),
-                            sub_rules: None,
-                            attribute: None,
-                        }]),
-                        attribute: None,
-                    }],
-                )
+                ScraperConfig::new(vec![
+                    ScrapeRule::one(".td-post-content", "content").sub_rules(vec![ScrapeRule::all("p", "paragraph")]),
+                ])
             }
         }
 
@@ -314,24 +263,9 @@ impl Default for NewsArticle {
     "#;
 
         let config = ScraperConfig::new(vec![
-                ScrapeRule::One {
-                    selector: "h1.title".to_string(),
-                    name: "title".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
-                ScrapeRule::One {
-                    selector: "div.author".to_string(),
-                    name: "author".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
-                ScrapeRule::All {
-                    selector: "div.paragraph".to_string(),
-                    name: "content".to_string(),
-                    sub_rules: None,
-                    attribute: None,
-                },
+                ScrapeRule::one("h1.title", "title"),
+                ScrapeRule::one("div.author", "author"),
+                ScrapeRule::all("div.paragraph", "content"),
             ],
         );
 
@@ -351,4 +285,3245 @@ impl Default for NewsArticle {
             ]
         );
     }
+
+    #[test]
+    fn test_microdata() {
+        let html = r#"
+        <div itemscope itemtype="https://schema.org/Product">
+            <h2 itemprop="name">Espresso Machine</h2>
+            <img itemprop="image" src="/img/espresso.jpg" alt="">
+            <a itemprop="url" href="https://example.com/espresso">Details</a>
+            <div itemprop="offers" itemscope itemtype="https://schema.org/Offer">
+                <meta itemprop="priceCurrency" content="EUR">
+                <span itemprop="price">249.00</span>
+            </div>
+        </div>
+    "#;
+
+        let config = ScraperConfig::new(vec![ScrapeRule::Microdata {
+            scope_selector: "[itemscope]".to_string(),
+            name: "product".to_string(),
+            options: RuleOptions::default(),
+        }]);
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(&config.to_string())
+            .build()
+            .scrape(html)
+            .unwrap();
+
+        let product: serde_json::Value = serde_json::from_str(&fields.0["product"]).unwrap();
+        assert_eq!(product["name"], "Espresso Machine");
+        assert_eq!(product["image"], "/img/espresso.jpg");
+        assert_eq!(product["url"], "https://example.com/espresso");
+        assert_eq!(product["offers"]["priceCurrency"], "EUR");
+        assert_eq!(product["offers"]["price"], "249.00");
+        assert!(product.get("price").is_none());
+    }
+
+    #[test]
+    fn test_require_absolute_url() {
+        let html = r#"
+        <html>
+            <body>
+                <a class="absolute" href="https://example.com/news">News</a>
+                <a class="relative" href="/a/b">Relative</a>
+            </body>
+        </html>
+    "#;
+
+        let link_config = |selector: &str| {
+            let mut rule = ScrapeRule::one(selector, "link").attribute("href");
+            if let ScrapeRule::One { require_absolute_url, .. } = &mut rule {
+                *require_absolute_url = true;
+            }
+            ScraperConfig::new(vec![rule]).to_string()
+        };
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(&link_config("a.absolute"))
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["link"], "https://example.com/news");
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(&link_config("a.relative"))
+            .with_base_url("https://example.com/x/")
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["link"], "https://example.com/a/b");
+
+        let result = HtmlScraperBuilder::new()
+            .with_config(&link_config("a.relative"))
+            .build()
+            .scrape::<Fields>(html);
+        assert!(matches!(
+            result,
+            Err(ScrapeError::RelativeUrl { name, value }) if name == "link" && value == "/a/b"
+        ));
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let html = r#"
+        <div class="product">
+            <span class="price">19.99</span>
+        </div>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            {
+                "type": "Coalesce",
+                "name": "price",
+                "candidates": [
+                    { "type": "One", "selector": ".product", "name": "from_attr", "attribute": "data-price" },
+                    { "type": "One", "selector": ".price", "name": "from_text" }
+                ]
+            }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["price"], "19.99");
+        assert!(!fields.0.contains_key("from_attr"));
+
+        // Candidates that matched nothing are passed over, whatever they yield
+        let config = r#"{ "rules": [{ "type": "Coalesce", "name": "price", "candidates": [
+            { "type": "Exists", "selector": ".sale", "name": "on_sale" },
+            { "type": "Count", "selector": ".sale", "name": "sales" },
+            { "type": "Text", "selector": ".sale", "name": "sale_price", "default": "n/a" },
+            { "type": "One", "selector": ".price", "name": "from_text" }
+        ] }] }"#;
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["price"], "19.99");
+    }
+
+    #[test]
+    fn test_scrape_with_provenance() {
+        let html = r#"
+        <html>
+            <body>
+                <h1 class="title">Breaking News</h1>
+                <div class="author">John Doe</div>
+                <div class="paragraph">This is the first paragraph.</div>
+                <div class="paragraph">This is the second paragraph.</div>
+            </body>
+        </html>
+    "#;
+
+        let fields = HtmlScraper::default()
+            .scrape_with_provenance::<NewsArticle>(html)
+            .unwrap();
+
+        let title = &fields["title"];
+        assert_eq!(title.value, "Breaking News");
+        assert_eq!(title.selector, "h1.title");
+        assert_eq!(title.source_tag, "h1");
+
+        let content = &fields["content"];
+        assert_eq!(content.selector, "div.paragraph");
+        assert_eq!(content.source_tag, "div");
+        assert_eq!(
+            serde_json::from_str::<Vec<String>>(&content.value).unwrap().len(),
+            2
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_size_limit() {
+        let scraper = HtmlScraperBuilder::new().with_regex_size_limit(16 * 1024).build();
+
+        assert!(scraper.regex(r"[0-9]+").is_ok());
+        assert!(matches!(
+            scraper.regex(r"\w{1000}"),
+            Err(ScrapeError::Config(ConfigError::RegexTooComplex(_)))
+        ));
+        assert!(matches!(scraper.regex("("), Err(ScrapeError::Regex(_))));
+
+        use html_parser::{RegexLimits, RegexReplaceCleaner};
+        let limits = RegexLimits { size_limit: Some(16 * 1024), ..Default::default() };
+        assert!(matches!(limits.compile(r"\w{1000}"), Err(ScrapeError::Config(ConfigError::RegexTooComplex(_)))));
+        assert!(matches!(RegexReplaceCleaner::new(r"\w{100000}", ""), Err(regex::Error::CompiledTooBig(_))));
+        assert!(matches!(
+            Validator::regex(r"\w{100000}"),
+            Err(ScrapeError::Config(ConfigError::RegexTooComplex(_)))
+        ));
+        assert!(matches!(Validator::regex("("), Err(ScrapeError::Regex(_))));
+
+        // Cleaners and validators compiled within the default limits are
+        // held to the builder's when it builds
+        #[derive(Debug, Deserialize)]
+        struct Words;
+
+        impl ScrapeConfig for Words {
+            fn get_config() -> ScraperConfig {
+                let words = Validator::regex(r"^\w{100}$").unwrap();
+                ScraperConfig::new(vec![ScrapeRule::text("p", "words")])
+                    .with_validators(HashMap::from([("words".to_string(), words)]))
+            }
+        }
+
+        let builder = || HtmlScraperBuilder::new().with_regex_size_limit(16 * 1024);
+        assert!(matches!(builder().try_build::<Words>(), Err(ConfigError::RegexTooComplex(_))));
+        assert!(HtmlScraperBuilder::new().try_build::<Words>().is_ok());
+
+        let cleaner = || ChainCleaner::new(vec![Arc::new(RegexReplaceCleaner::new(r"\w{100}", "").unwrap())]);
+        let config = r#"{ "rules": [{ "type": "Text", "selector": "p", "name": "text", "cleaner": "words" }] }"#;
+        let result = builder()
+            .with_config(config)
+            .register_cleaner("words", cleaner())
+            .try_build::<Fields>();
+        assert!(matches!(result, Err(ConfigError::RegexTooComplex(_))));
+        assert!(matches!(
+            builder().with_cleaner(cleaner()).try_build::<Fields>(),
+            Err(ConfigError::RegexTooComplex(_))
+        ));
+        assert!(builder().with_config(config).try_build::<Fields>().is_ok());
+    }
+
+    #[test]
+    fn test_shared_cleaner() {
+        let html = r#"<h1 class="title">
+            Breaking
+            News
+        </h1>"#;
+
+        let cleaner: Arc<dyn TextCleaner> = Arc::new(DefaultCleaner);
+        let scraper = HtmlScraperBuilder::new()
+            .with_cleaner_arc(cleaner.clone())
+            .build();
+        let cloned = scraper.clone();
+
+        // Our handle, the original scraper and its clone
+        assert_eq!(Arc::strong_count(&cleaner), 3);
+
+        let article: NewsArticle = scraper.scrape(html).unwrap();
+        let cloned_article: NewsArticle = cloned.scrape(html).unwrap();
+        assert_eq!(article.title, "Breaking News");
+        assert_eq!(cloned_article.title, "Breaking News");
+    }
+
+    #[test]
+    fn test_input_value() {
+        let html = r#"
+        <form>
+            <input name="email" value="jane@example.com">
+            <textarea name="bio">Hello there</textarea>
+            <select name="country">
+                <option value="no">Norway</option>
+                <option value="se" selected>Sweden</option>
+            </select>
+            <select name="size">
+                <option>Small</option>
+                <option>Large</option>
+            </select>
+        </form>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "InputValue", "selector": "input[name=email]", "name": "email" },
+            { "type": "InputValue", "selector": "textarea", "name": "bio" },
+            { "type": "InputValue", "selector": "select[name=country]", "name": "country" },
+            { "type": "InputValue", "selector": "select[name=size]", "name": "size" }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["email"], "jane@example.com");
+        assert_eq!(fields.0["bio"], "Hello there");
+        assert_eq!(fields.0["country"], "se");
+        assert_eq!(fields.0["size"], "Small");
+    }
+
+    #[test]
+    fn test_scrape_stream() {
+        let html = r#"
+        <div id="search">
+            <div class="g"><h3>Title 1</h3><cite>www.example.com/1</cite></div>
+            <div class="g"><h3>Title 2</h3><cite>www.example.com/2</cite></div>
+            <div class="g"><h3>Title 3</h3><cite>www.example.com/3</cite></div>
+        </div>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "One", "selector": "h3", "name": "title" },
+            { "type": "One", "selector": "cite", "name": "url" }
+        ]
+    }
+    "#;
+
+        let lines: Vec<String> = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape_stream::<Fields>(html, ".g")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(lines.len(), 3);
+        for (i, line) in lines.iter().enumerate() {
+            assert!(!line.contains('\n'));
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(record["title"], format!("Title {}", i + 1));
+            assert_eq!(record["url"], format!("www.example.com/{}", i + 1));
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_attribute_value() {
+        let html = r#"
+        <ul>
+            <li data-state="Active">First</li>
+            <li data-state="inactive">Second</li>
+            <li data-state="ACTIVE">Third</li>
+        </ul>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "All", "selector": "li[data-state=\"active\" i]", "name": "active" },
+            { "type": "All", "selector": "li[data-state=\"active\"]", "name": "exact" }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["active"], r#"["First","Third"]"#);
+        assert_eq!(fields.0["exact"], "[]");
+    }
+
+    #[test]
+    fn test_config_check() {
+        assert!(NewsArticle::get_config().check().is_ok());
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "One", "selector": "h1.title", "name": "title" },
+            {
+                "type": "One",
+                "selector": "article",
+                "name": "body",
+                "sub_rules": [{ "type": "Text", "selector": "p[", "name": "text" }]
+            }
+        ]
+    }
+    "#;
+        let config: ScraperConfig = serde_json::from_str(config).unwrap();
+        assert!(matches!(
+            config.check(),
+            Err(ConfigError::InvalidSelector(selector)) if selector == "p["
+        ));
+    }
+
+    #[test]
+    fn test_number_cleaner() {
+        assert_eq!(NumberCleaner::us().clean("1,234.56"), "1234.56");
+        assert_eq!(NumberCleaner::eu().clean("1.234,56"), "1234.56");
+        assert_eq!(NumberCleaner::us().clean("$1,234.56 USD"), "1234.56");
+        assert_eq!(NumberCleaner::eu().clean("€ 1.234,56 kg"), "1234.56");
+        assert_eq!(NumberCleaner::new(' ', ',').clean("-1 234,5"), "-1234.5");
+        assert_eq!(NumberCleaner::us().clean("Sold out"), "");
+
+        // Only a sign right before the number, give or take a currency
+        // symbol or a space, makes it negative
+        assert_eq!(NumberCleaner::eu().clean("Sale - €12,99"), "12.99");
+        assert_eq!(NumberCleaner::us().clean("Mon-Fri: 9 to 5"), "9");
+        assert_eq!(NumberCleaner::us().clean("-$5.00"), "-5.00");
+        assert_eq!(NumberCleaner::us().clean("\u{2212} 3"), "-3");
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let html = r#"
+        <nav>
+            <a href="/news">News</a>
+            <a href="sports/">Sports</a>
+            <a href="https://other.example.org/">Partner</a>
+            <a href="/news">News again</a>
+            <a href="mailto:editor@example.com">Mail us</a>
+            <a href="tel:5551234">Call us</a>
+            <a href="tel:911">Emergency</a>
+            <a href="javascript:void(0)">Menu</a>
+            <a href="">Empty</a>
+            <a>No href</a>
+        </nav>
+    "#;
+
+        let links = HtmlScraper::default()
+            .extract_links(html, Some("https://example.com/section/"))
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                "https://example.com/news",
+                "https://example.com/section/sports/",
+                "https://other.example.org/",
+            ]
+        );
+
+        let links = HtmlScraper::default().extract_links(html, None).unwrap();
+        assert_eq!(links, vec!["/news", "sports/", "https://other.example.org/"]);
+
+        assert!(matches!(
+            HtmlScraper::default().extract_links(html, Some("example.com")),
+            Err(ScrapeError::InvalidBaseUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_skip_script_style() {
+        let html = r#"
+        <div class="article">
+            <p>Visible text.</p>
+            <script>window.track("page");</script>
+            <style>.article { color: red; }</style>
+            <p>More text.</p>
+        </div>
+        <script type="application/ld+json">{"@type": "NewsArticle"}</script>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "One", "selector": ".article", "name": "body" },
+            { "type": "One", "selector": "script[type=\"application/ld+json\"]", "name": "ld_json" }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["body"], "Visible text. More text.");
+        assert_eq!(fields.0["ld_json"], r#"{"@type": "NewsArticle"}"#);
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .skip_script_style(false)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert!(fields.0["body"].contains("window.track"));
+    }
+
+    #[test]
+    fn test_normalize_attribute_names() {
+        let html = r#"<div class="item" data-ID="42">Item</div>"#;
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "One", "selector": ".item", "name": "lower", "attribute": "data-id" },
+            { "type": "One", "selector": ".item", "name": "mixed", "attribute": "data-Id" }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["lower"], "42");
+        assert_eq!(fields.0["mixed"], "");
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .normalize_attribute_names(true)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["lower"], "42");
+        assert_eq!(fields.0["mixed"], "42");
+    }
+
+    #[test]
+    fn test_next_sibling_text() {
+        let html = r#"
+        <dl>
+            <dt>Author</dt>
+            <dd>John Doe</dd>
+            <dt>Published</dt>
+            <dd>2024-05-01</dd>
+            <dt>Tags</dt>
+        </dl>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "All", "selector": "dt", "name": "labels" },
+            { "type": "NextSiblingText", "selector": "dt", "name": "values" }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["labels"], r#"["Author","Published","Tags"]"#);
+        assert_eq!(fields.0["values"], r#"["John Doe","2024-05-01",""]"#);
+    }
+
+    #[test]
+    fn test_config_file_unknown_extension() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("html_parser_{}.config", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{ "rules": [{ "type": "One", "selector": "h1.title", "name": "title" }] }"#,
+        )
+        .unwrap();
+        let invalid = dir.join(format!("html_parser_{}.txt", std::process::id()));
+        std::fs::write(&invalid, "rules: nope").unwrap();
+
+        let article: Result<NewsArticle, _> = HtmlScraperBuilder::new()
+            .with_config(path.to_str().unwrap())
+            .build()
+            .scrape(r#"<h1 class="title">Breaking News</h1>"#);
+        let unsupported = NewsArticle::from_config(invalid.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&invalid).unwrap();
+
+        assert_eq!(article.unwrap().title, "Breaking News");
+        assert!(matches!(unsupported, Err(ConfigError::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn test_boolean_attributes() {
+        let html = r#"
+        <form>
+            <input type="checkbox" class="newsletter" checked>
+            <input type="checkbox" class="terms" checked="checked">
+            <input type="checkbox" class="offers">
+        </form>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            { "type": "One", "selector": ".newsletter", "name": "newsletter", "attribute": "checked" },
+            { "type": "One", "selector": ".offers", "name": "offers", "attribute": "checked" },
+            { "type": "All", "selector": "input", "name": "all", "attribute": "checked" }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_boolean_attributes(HTML_BOOLEAN_ATTRIBUTES)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["newsletter"], "true");
+        assert_eq!(fields.0["offers"], "false");
+        assert_eq!(fields.0["all"], r#"["true","true","false"]"#);
+
+        // Without the option the raw attribute values come through
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["all"], r#"["","checked",""]"#);
+    }
+
+    #[test]
+    fn test_signature() {
+        let html = r#"
+        <html>
+            <body>
+                <h1 class="title">Breaking News</h1>
+                <div class="paragraph">This is the first paragraph.</div>
+                <div class="paragraph">This is the second paragraph.</div>
+                <div class="paragraph">This is the third paragraph.</div>
+            </body>
+        </html>
+    "#;
+
+        let signature = HtmlScraper::default().signature::<NewsArticle>(html).unwrap();
+        assert_eq!(
+            signature,
+            HashMap::from([
+                ("h1.title".to_string(), 1),
+                ("div.author".to_string(), 0),
+                ("div.paragraph".to_string(), 3),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        let html = r#"
+        <table>
+            <tr id="row-1"><td class="name">Alice</td><td class="score">10</td></tr>
+            <tr id="row-2"><td class="name">Bob</td><td class="score">8</td></tr>
+            <tr id="row-3"><td class="name">Carol</td><td class="score">7</td></tr>
+            <tr id="row-4"><td class="name">Dave</td><td class="score">3</td></tr>
+        </table>
+    "#;
+
+        let config = r##"
+    {
+        "rules": [
+            {
+                "type": "Repeat",
+                "selector_template": "#row-{i}",
+                "name": "top",
+                "count": 3,
+                "sub_rules": [
+                    { "type": "One", "selector": ".name", "name": "name" },
+                    { "type": "One", "selector": ".score", "name": "score" }
+                ]
+            }
+        ]
+    }
+    "##;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        let rows: Vec<String> = serde_json::from_str(&fields.0["top"]).unwrap();
+        let rows: Vec<HashMap<String, String>> = rows
+            .iter()
+            .map(|row| serde_json::from_str(row).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["name"], "Alice");
+        assert_eq!(rows[2]["name"], "Carol");
+        assert_eq!(rows[2]["score"], "7");
+    }
+
+    #[test]
+    fn test_scrape_deserialize() {
+        let html = r#"
+        <html>
+            <body>
+                <h1 class="title">Breaking News</h1>
+                <div class="author">John Doe</div>
+            </body>
+        </html>
+    "#;
+
+        #[derive(Debug, Deserialize)]
+        pub struct Byline {
+            title: String,
+            author: String,
+        }
+
+        impl ScrapeConfig for Byline {
+            fn get_config() -> ScraperConfig {
+                ScraperConfig::new(vec![
+                    ScrapeRule::text("h1.title", "title"),
+                    ScrapeRule::text("div.author", "author"),
+                ])
+            }
+        }
+
+        let byline: Byline = HtmlScraper::default().scrape_deserialize(html).unwrap();
+        assert_eq!(byline.title, "Breaking News");
+        assert_eq!(byline.author, "John Doe");
+
+        let value = HtmlScraper::default().scrape_value::<Byline>(html).unwrap();
+        let title: &str = value["title"].as_str().unwrap();
+        assert_eq!(title, "Breaking News");
+    }
+
+    #[test]
+    fn test_max_total_matches() {
+        let html = r#"
+        <html>
+            <body>
+                <h1 class="title">Breaking News</h1>
+                <div class="paragraph">This is the first paragraph.</div>
+                <div class="paragraph">This is the second paragraph.</div>
+                <div class="paragraph">This is the third paragraph.</div>
+            </body>
+        </html>
+    "#;
+
+        let result = HtmlScraperBuilder::new()
+            .with_max_total_matches(2)
+            .build()
+            .scrape::<NewsArticle>(html);
+        assert!(matches!(result, Err(ScrapeError::MatchLimitExceeded(2))));
+
+        let article: NewsArticle = HtmlScraperBuilder::new()
+            .with_max_total_matches(3)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(article.content.len(), 3);
+    }
+
+    #[test]
+    fn test_closest_ancestor() {
+        let html = r#"
+        <div class="card" data-id="17">
+            <div class="body">
+                <div class="actions"><button class="buy">Buy</button></div>
+            </div>
+        </div>
+        <div class="card" data-id="23">
+            <div class="actions"><button class="buy">Buy</button></div>
+        </div>
+        <button class="buy">Orphan</button>
+    "#;
+
+        let config = r#"
+    {
+        "rules": [
+            {
+                "type": "ClosestAncestor",
+                "selector": "button.buy",
+                "ancestor_selector": ".card",
+                "name": "card_ids",
+                "attribute": "data-id"
+            }
+        ]
+    }
+    "#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["card_ids"], r#"["17","23",""]"#);
+    }
+
+    #[test]
+    fn test_to_tsv() {
+        let records: Vec<HashMap<String, String>> = vec![
+            HashMap::from([
+                ("title".to_string(), "First".to_string()),
+                ("author".to_string(), "Jane".to_string()),
+            ]),
+            HashMap::from([
+                ("title".to_string(), "Second\tpart".to_string()),
+                ("author".to_string(), "John".to_string()),
+            ]),
+            HashMap::from([("title".to_string(), "Third\nline".to_string())]),
+        ];
+
+        assert_eq!(
+            to_tsv(&records),
+            "author\ttitle\nJane\tFirst\nJohn\tSecond\\tpart\n\tThird\\nline\n"
+        );
+        assert_eq!(
+            to_csv(&records),
+            "author,title\nJane,First\nJohn,Second\tpart\n,\"Third\nline\"\n"
+        );
+    }
+
+    #[test]
+    fn test_raw_text() {
+        let html = r#"<p class="note">Fish &amp; <em>chips</em> &lt;3</p>"#;
+
+        let config = r#"{ "rules": [{ "type": "One", "selector": ".note", "name": "note" }] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["note"], "Fish & chips <3");
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .raw_text(true)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["note"], "Fish &amp; chips &lt;3");
+    }
+
+    #[test]
+    fn test_page_fingerprint() {
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "One", "selector": ".body", "name": "body" }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let page = r#"<h1>Launch day</h1>
+            <div class="ad">Buy now!</div>
+            <p class="body">The rocket  left on time.</p>"#;
+        let same_content = r#"<header><h1>Launch day</h1></header>
+            <aside class="ad"><a href="/deal">Limited offer</a></aside>
+            <p class="body">The rocket
+                left on time.</p>"#;
+        let other_content = r#"<h1>Launch day</h1>
+            <p class="body">The rocket was delayed.</p>"#;
+
+        let fingerprint = scraper.page_fingerprint::<Fields>(page).unwrap();
+        assert_eq!(fingerprint, scraper.page_fingerprint::<Fields>(same_content).unwrap());
+        assert_ne!(fingerprint, scraper.page_fingerprint::<Fields>(other_content).unwrap());
+    }
+
+    #[test]
+    fn test_config_from_rules() {
+        let config: ScraperConfig = vec![ScrapeRule::text("h1", "title")]
+        .into();
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(&config.to_string())
+            .build()
+            .scrape("<h1>Hello</h1>")
+            .unwrap();
+        assert_eq!(fields.0["title"], "Hello");
+    }
+
+    #[test]
+    fn test_attribute_from() {
+        let html = r#"
+        <div class="product"><a href="/p/1"><img src="/img/1.jpg"></a></div>
+        <div class="product"><span>No image</span></div>
+        <div class="product"><img src="/img/3.jpg"></div>
+    "#;
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": ".product", "name": "first_image",
+              "attribute": "src", "attribute_from": "img" },
+            { "type": "All", "selector": ".product", "name": "images",
+              "attribute": "src", "attribute_from": "img" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["first_image"], "/img/1.jpg");
+        assert_eq!(fields.0["images"], r#"["/img/1.jpg","/img/3.jpg"]"#);
+    }
+
+    #[test]
+    fn test_outline() {
+        let html = r#"
+        <h1>Guide</h1>
+        <h2>Install</h2>
+        <h3>  From
+            source </h3>
+        <h3></h3>
+        <h2>Usage</h2>
+        <h3>Examples</h3>
+        <h2>Usage</h2>
+        <div class="card"><h4>Note <span><h5>Inner</h5></span></h4></div>
+    "#;
+
+        let outline = HtmlScraper::default().outline(html);
+        assert_eq!(
+            outline,
+            vec![
+                (1, "Guide".to_string()),
+                (2, "Install".to_string()),
+                (3, "From source".to_string()),
+                (2, "Usage".to_string()),
+                (3, "Examples".to_string()),
+                (2, "Usage".to_string()),
+                (4, "Note".to_string()),
+                (5, "Inner".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_document_element() {
+        let html = r#"<!DOCTYPE html><html lang="en" dir="ltr"><body><p>Hi</p></body></html>"#;
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "html", "name": "lang", "attribute": "lang" },
+            { "type": "One", "selector": ":root", "name": "dir", "attribute": "dir" },
+            { "type": "All", "selector": "[lang]", "name": "localized", "attribute": "lang" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["lang"], "en");
+        assert_eq!(fields.0["dir"], "ltr");
+        assert_eq!(fields.0["localized"], r#"["en"]"#);
+
+        let scraper = HtmlScraper::default();
+        assert_eq!(scraper.lang(html), Some("en".to_string()));
+        assert_eq!(scraper.lang("<p>No language</p>"), None);
+    }
+
+    #[test]
+    fn test_validators() {
+        #[derive(Debug, Deserialize)]
+        struct Price;
+
+        impl ScrapeConfig for Price {
+            fn get_config() -> ScraperConfig {
+                let numeric = Validator::from_fn(|value| {
+                    !value.is_empty() && value.chars().all(|c| c.is_ascii_digit() || c == '.')
+                });
+                ScraperConfig::new(vec![ScrapeRule::text(".price", "price")])
+                .with_validators(HashMap::from([("price".to_string(), numeric)]))
+            }
+        }
+
+        let scraper = HtmlScraper::default();
+        let value = scraper
+            .scrape_value::<Price>(r#"<span class="price">19.99</span>"#)
+            .unwrap();
+        assert_eq!(value["price"], "19.99");
+
+        let result = scraper.scrape_value::<Price>(r#"<span class="price">Call us</span>"#);
+        assert!(matches!(
+            result,
+            Err(ScrapeError::ValidationFailed { name, value }) if name == "price" && value == "Call us"
+        ));
+
+        // With several failing, the first in rule order is reported
+        #[derive(Debug, Deserialize)]
+        struct Listing;
+
+        impl ScrapeConfig for Listing {
+            fn get_config() -> ScraperConfig {
+                let names = ["title", "price", "seller", "location", "date", "condition"];
+                let rules = names.iter().map(|name| ScrapeRule::text(&format!(".{name}"), name)).collect();
+                let validators = names.iter().map(|name| (name.to_string(), Validator::from_fn(|_| false)));
+                ScraperConfig::new(rules).with_validators(validators.collect())
+            }
+        }
+
+        let html = r#"<span class="title">Bike</span><span class="price">50</span><span class="date">Today</span>"#;
+        for _ in 0..10 {
+            let result = scraper.scrape_value::<Listing>(html);
+            assert!(matches!(result, Err(ScrapeError::ValidationFailed { name, .. }) if name == "title"));
+        }
+    }
+
+    #[test]
+    fn test_own_text() {
+        let html = r#"<div class="spec">
+            Weight: <b>1.2 kg</b> (approx.)
+        </div>"#;
+
+        let config = r#"{ "rules": [
+            { "type": "OwnText", "selector": ".spec", "name": "own" },
+            { "type": "OwnText", "selector": ".spec", "name": "label", "index": 0 },
+            { "type": "OwnText", "selector": ".spec", "name": "note", "index": 1 },
+            { "type": "OwnText", "selector": ".spec", "name": "missing", "index": 2 }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["own"], "Weight:  (approx.)");
+        assert_eq!(fields.0["label"], "Weight:");
+        assert_eq!(fields.0["note"], "(approx.)");
+        assert!(!fields.0.contains_key("missing"));
+    }
+
+    #[cfg(feature = "jsonpath")]
+    #[test]
+    fn test_scrape_jsonpath() {
+        let html = r#"
+        <h1>Front page</h1>
+        <article><h2>First</h2><span class="tag">a</span></article>
+        <article><h2>Second</h2><span class="tag">b</span><span class="tag">c</span></article>
+    "#;
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "All", "selector": "article", "name": "articles", "sub_rules": [
+                { "type": "One", "selector": "h2", "name": "title" },
+                { "type": "All", "selector": ".tag", "name": "tags" }
+            ] }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let titles = scraper.scrape_jsonpath::<Fields>(html, "$..title").unwrap();
+        assert_eq!(titles.len(), 3);
+        for title in ["Front page", "First", "Second"] {
+            assert!(titles.contains(&serde_json::json!(title)));
+        }
+
+        let tags = scraper.scrape_jsonpath::<Fields>(html, "$.articles[-1].tags[*]").unwrap();
+        assert_eq!(tags, vec![serde_json::json!("b"), serde_json::json!("c")]);
+
+        assert!(matches!(
+            scraper.scrape_jsonpath::<Fields>(html, "articles"),
+            Err(ScrapeError::InvalidJsonPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_data_uri_attribute() {
+        let html = r#"
+        <img class="pixel" src="data:image/png;base64,iVBORw0KGgo=">
+        <img class="svg" src="data:image/svg+xml,%3Csvg%2F%3E">
+        <img class="remote" src="/logo.png">
+    "#;
+
+        let rule = |selector: &str| {
+            format!(
+                r#"{{ "rules": [{{ "type": "One", "selector": "{}", "name": "image",
+                    "attribute": "src", "decode": "DataUri" }}] }}"#,
+                selector
+            )
+        };
+        let scrape = |selector: &str| -> Result<Fields, ScrapeError> {
+            HtmlScraperBuilder::new()
+                .with_config(&rule(selector))
+                .build()
+                .scrape(html)
+        };
+
+        let image: serde_json::Value = serde_json::from_str(&scrape(".pixel").unwrap().0["image"]).unwrap();
+        assert_eq!(image, serde_json::json!({ "mime": "image/png", "base64": "iVBORw0KGgo=" }));
+
+        // Percent-encoded payloads are base64-encoded too
+        let image: serde_json::Value = serde_json::from_str(&scrape(".svg").unwrap().0["image"]).unwrap();
+        assert_eq!(image, serde_json::json!({ "mime": "image/svg+xml", "base64": "PHN2Zy8+" }));
+
+        assert!(matches!(scrape(".remote"), Err(ScrapeError::InvalidDataUri(name)) if name == "image"));
+    }
+
+    #[test]
+    fn test_boxed_cleaner() {
+        let html = r#"<span class="price">1.234,50 kr</span>"#;
+        let config = r#"{ "rules": [{ "type": "One", "selector": ".price", "name": "price" }] }"#;
+
+        let cleaner_for = |name: &str| -> Box<dyn TextCleaner> {
+            match name {
+                "eu_number" => Box::new(NumberCleaner::eu()),
+                _ => Box::new(DefaultCleaner),
+            }
+        };
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_boxed_cleaner(cleaner_for("eu_number"))
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["price"], "1234.50");
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_boxed_cleaner(cleaner_for("default"))
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["price"], "1.234,50 kr");
+    }
+
+    #[test]
+    fn test_scrape_batch() {
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "a", "name": "link", "attribute": "href",
+              "require_absolute_url": true }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let docs = vec![
+            r#"<a href="https://example.com/1">One</a>"#.to_string(),
+            r#"<a href="/2">Two</a>"#.to_string(),
+            r#"<a href="https://example.com/3">Three</a>"#.to_string(),
+        ];
+        let results = scraper.scrape_batch::<Fields>(docs);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().0["link"], "https://example.com/1");
+        assert!(matches!(results[1], Err(ScrapeError::RelativeUrl { .. })));
+        assert_eq!(results[2].as_ref().unwrap().0["link"], "https://example.com/3");
+
+        let results = HtmlScraperBuilder::new()
+            .with_config("not a config")
+            .build()
+            .scrape_batch::<Fields>(vec!["<p></p>".to_string(); 2]);
+        assert!(results.iter().all(|result| matches!(result, Err(ScrapeError::Config(ConfigError::UnsupportedFormat)))));
+    }
+
+    #[test]
+    fn test_stable_serialization() {
+        let html = r#"
+        <h1>Title</h1>
+        <p class="lead">Lead</p>
+        <ul><li><b>b</b><i>i</i><a href="/a">a</a></li><li><b>c</b><i>j</i><a href="/b">b</a></li></ul>
+        <span class="author">Jane</span>
+    "#;
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "All", "selector": "li", "name": "items", "sub_rules": [
+                { "type": "One", "selector": "i", "name": "italic" },
+                { "type": "One", "selector": "b", "name": "bold" },
+                { "type": "One", "selector": "a", "name": "link", "attribute": "href" }
+            ] },
+            { "type": "One", "selector": "body", "name": "meta", "sub_rules": [
+                { "type": "One", "selector": ".lead", "name": "lead" },
+                { "type": "One", "selector": ".author", "name": "author" }
+            ] }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let first = to_pretty_string(&scraper.scrape_value::<Fields>(html).unwrap());
+        let second = to_pretty_string(&scraper.scrape_value::<Fields>(html).unwrap());
+        assert_eq!(first, second);
+
+        // Fields follow the rule declaration order
+        let value = scraper.scrape_value::<Fields>(html).unwrap();
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["title", "items", "lead", "author"]);
+        assert_eq!(
+            value["items"].to_string(),
+            r#"[{"italic":"i","bold":"b","link":"/a"},{"italic":"j","bold":"c","link":"/b"}]"#
+        );
+    }
+
+    #[test]
+    fn test_dynamic_key() {
+        let html = r#"
+        <ul class="specs">
+            <li data-key="weight" data-value="1.2 kg">Weight</li>
+            <li data-key="color" data-value="Red">Color</li>
+            <li data-key="weight" data-value="2 kg">Weight (boxed)</li>
+            <li data-key="notes">No value</li>
+        </ul>
+    "#;
+
+        let config = r#"{ "rules": [{ "type": "DynamicKey", "selector": ".specs li", "name": "specs",
+            "key_attribute": "data-key", "value_attribute": "data-value" }] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["specs"], r#"{"weight":"1.2 kg","color":"Red"}"#);
+    }
+
+    #[test]
+    fn test_with_length() {
+        let html = r#"<h1>Crème brûlée</h1><ul><li>Ünïcödé</li><li>ok</li></ul>"#;
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title", "with_length": true },
+            { "type": "All", "selector": "li", "name": "items", "with_length": true }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+
+        let title: serde_json::Value = serde_json::from_str(&fields.0["title"]).unwrap();
+        assert_eq!(title["value"], "Crème brûlée");
+        assert_eq!(title["length"], "Crème brûlée".chars().count());
+        assert_eq!(title["length"], 12);
+
+        let items: serde_json::Value = serde_json::from_str(&fields.0["items"]).unwrap();
+        assert_eq!(items, serde_json::json!([
+            { "value": "Ünïcödé", "length": 7 },
+            { "value": "ok", "length": 2 }
+        ]));
+    }
+
+    #[test]
+    fn test_main_content() {
+        let html = r#"
+        <html><body>
+            <nav>
+                <p><a href="/">Home</a> <a href="/news">News</a> <a href="/sports">Sports</a>
+                   <a href="/weather">Weather</a> <a href="/contact">Contact us</a></p>
+            </nav>
+            <div class="layout">
+                <aside class="sidebar">
+                    <p><a href="/popular/1">The ten most popular stories this week</a></p>
+                    <p><a href="/popular/2">Another story everybody is reading</a></p>
+                </aside>
+                <article>
+                    <h1>Harbour reopens</h1>
+                    <p>The harbour reopened on Monday after three weeks of repairs to the pier.</p>
+                    <p>Fishing boats were the first to return, followed by the <a href="/ferry">ferry</a>.</p>
+                    <p>Officials expect traffic to be back to normal by the end of the month.</p>
+                </article>
+            </div>
+            <footer><p>&copy; 2024 Example News</p></footer>
+        </body></html>
+    "#;
+
+        let text = HtmlScraper::default().main_content(html).unwrap();
+        assert!(text.starts_with("Harbour reopens The harbour reopened on Monday"));
+        assert!(text.ends_with("back to normal by the end of the month."));
+        assert!(!text.contains("popular"));
+        assert!(!text.contains("Contact us"));
+
+        let text = HtmlScraper::default().main_content("<body><div> Just  text </div></body>").unwrap();
+        assert_eq!(text, "Just text");
+    }
+
+    #[test]
+    fn test_scrape_tagged() {
+        let html = r#"
+        <h1>Desk lamp</h1>
+        <p class="description">A lamp for your desk.</p>
+        <ul><li>LED</li><li>USB powered</li></ul>
+    "#;
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "One", "selector": ".description", "name": "description", "tags": ["detail"] },
+            { "type": "All", "selector": "li", "name": "features", "tags": ["detail", "specs"] }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let summary: Fields = scraper.scrape_tagged(html, &["summary"]).unwrap();
+        assert_eq!(summary.0.len(), 1);
+        assert_eq!(summary.0["title"], "Desk lamp");
+
+        let detail: Fields = scraper.scrape_tagged(html, &["detail"]).unwrap();
+        assert_eq!(detail.0.len(), 3);
+        assert_eq!(detail.0["description"], "A lamp for your desk.");
+
+        let specs: Fields = scraper.scrape_tagged(html, &["specs"]).unwrap();
+        assert!(specs.0.contains_key("features"));
+        assert!(!specs.0.contains_key("description"));
+
+        // Plain `scrape` ignores tags
+        let full: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(full.0.len(), 3);
+    }
+
+    #[test]
+    fn test_feed_items() {
+        let feed = r#"<?xml version="1.0"?>
+        <rss version="2.0"><channel>
+            <title>Example feed</title>
+            <item>
+                <title>First post</title>
+                <link>https://example.com/1</link>
+                <pubDate>Mon, 01 Jan 2024 10:00:00 GMT</pubDate>
+                <description><![CDATA[<b>Bold</b> intro]]></description>
+                <enclosure url="https://example.com/1.mp3" type="audio/mpeg"/>
+                <guid>1</guid>
+            </item>
+            <item>
+                <title>Second &amp; last</title>
+                <link>https://example.com/2</link>
+                <pubDate>Tue, 02 Jan 2024 10:00:00 GMT</pubDate>
+                <guid>2</guid>
+            </item>
+        </channel></rss>"#;
+
+        // Custom tags work with the plain HTML parser
+        let titles = r#"{ "rules": [{ "type": "All", "selector": "item > title", "name": "titles" }] }"#;
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(titles)
+            .build()
+            .scrape(feed)
+            .unwrap();
+        assert_eq!(fields.0["titles"], r#"["First post","Second & last"]"#);
+
+        let config = r#"{ "rules": [{ "type": "All", "selector": "item", "name": "items", "sub_rules": [
+            { "type": "One", "selector": "title", "name": "title" },
+            { "type": "One", "selector": "x-link", "name": "link" },
+            { "type": "One", "selector": "pubDate", "name": "published" },
+            { "type": "One", "selector": "description", "name": "description" },
+            { "type": "One", "selector": "enclosure", "name": "audio", "attribute": "url" },
+            { "type": "One", "selector": "guid", "name": "guid" }
+        ] }] }"#;
+        let value = HtmlScraperBuilder::new()
+            .with_config(config)
+            .parse_xml(true)
+            .build()
+            .scrape_value::<Fields>(feed)
+            .unwrap();
+
+        let first = &value["items"][0];
+        assert_eq!(first["title"], "First post");
+        assert_eq!(first["link"], "https://example.com/1");
+        assert_eq!(first["published"], "Mon, 01 Jan 2024 10:00:00 GMT");
+        assert_eq!(first["description"], "<b>Bold</b> intro");
+        assert_eq!(first["audio"], "https://example.com/1.mp3");
+        assert_eq!(first["guid"], "1");
+
+        let second = &value["items"][1];
+        assert_eq!(second["title"], "Second & last");
+        assert_eq!(second["link"], "https://example.com/2");
+    }
+
+    #[test]
+    fn test_scrape_deserialize_typed() {
+        let html = r#"
+        <h1>Desk lamp</h1>
+        <span class="stock">12</span>
+        <span class="price">19.5</span>
+        <ul><li>LED</li><li>USB powered</li></ul>
+        <div class="review"><b>Ann</b><i>5</i></div>
+        <div class="review"><b>Bo</b><i>3</i></div>
+    "#;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Review {
+            author: String,
+            stars: u8,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Product {
+            title: String,
+            stock: u32,
+            price: f64,
+            discount: Option<String>,
+            features: Vec<String>,
+            reviews: Vec<Review>,
+        }
+
+        impl ScrapeConfig for Product {
+            fn get_config() -> ScraperConfig {
+                serde_json::from_str(r#"{ "rules": [
+                    { "type": "One", "selector": "h1", "name": "title" },
+                    { "type": "One", "selector": ".stock", "name": "stock" },
+                    { "type": "One", "selector": ".price", "name": "price" },
+                    { "type": "One", "selector": ".discount", "name": "discount" },
+                    { "type": "All", "selector": "li", "name": "features" },
+                    { "type": "All", "selector": ".review", "name": "reviews", "sub_rules": [
+                        { "type": "One", "selector": "b", "name": "author" },
+                        { "type": "One", "selector": "i", "name": "stars" }
+                    ] }
+                ] }"#)
+                .unwrap()
+            }
+        }
+
+        let product: Product = HtmlScraper::default().scrape_deserialize(html).unwrap();
+        assert_eq!(product.title, "Desk lamp");
+        assert_eq!(product.stock, 12);
+        assert_eq!(product.price, 19.5);
+        assert_eq!(product.discount, None);
+        assert_eq!(product.features, ["LED", "USB powered"]);
+        assert_eq!(
+            product.reviews,
+            [
+                Review { author: "Ann".to_string(), stars: 5 },
+                Review { author: "Bo".to_string(), stars: 3 },
+            ]
+        );
+
+        let result = HtmlScraper::default()
+            .scrape_deserialize::<Product>(&html.replace("<span class=\"stock\">12", "<span class=\"stock\">many"));
+        assert!(matches!(result, Err(ScrapeError::Deserialize(_))));
+    }
+
+    #[test]
+    fn test_paragraph_separator() {
+        let html = r#"
+        <article>
+            <p>First paragraph,
+               wrapped over two lines.</p>
+            <p>   </p>
+            <p>Second paragraph.</p>
+            <p>Third paragraph.</p>
+        </article>
+    "#;
+
+        let config = r#"{ "rules": [{ "type": "Text", "selector": "article p", "name": "body",
+            "paragraph_separator": "\n\n" }] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(
+            fields.0["body"],
+            "First paragraph, wrapped over two lines.\n\nSecond paragraph.\n\nThird paragraph."
+        );
+    }
+
+    #[test]
+    fn test_completeness() {
+        let html = r#"
+        <h1>Desk lamp</h1>
+        <span class="price"> </span>
+        <ul class="features"><li>LED</li></ul>
+    "#;
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "One", "selector": ".price", "name": "price" },
+            { "type": "One", "selector": ".sku", "name": "sku" },
+            { "type": "All", "selector": ".features li", "name": "features" }
+        ] }"#;
+
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        assert_eq!(scraper.completeness::<Fields>(html).unwrap(), 0.5);
+        assert_eq!(scraper.completeness::<Fields>("<p>Gone</p>").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_text_skip() {
+        let html = r#"
+        <article>
+            <p>Lead paragraph.</p>
+            <p>Second.</p>
+            <p>Third.</p>
+        </article>
+    "#;
+
+        let config = r#"{ "rules": [
+            { "type": "Text", "selector": "article p", "name": "rest", "skip": 1 },
+            { "type": "Text", "selector": "article p", "name": "none", "skip": 5 }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["rest"], "Second. Third.");
+        assert_eq!(fields.0["none"], "");
+    }
+
+    #[test]
+    fn test_preserve_preformatted() {
+        let html = "<article>\n    <p>Install   it\n  with:</p>\n<pre>cargo add html_parser\n    --features regex</pre>\n    <p>Then call <code>HtmlScraper::new()</code>   and   build.</p>\n</article>\n<pre class=\"block\">  a\n    b</pre>";
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "article", "name": "article" },
+            { "type": "One", "selector": "pre.block", "name": "block" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .preserve_preformatted(true)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(
+            fields.0["article"],
+            "Install   it with:\ncargo add html_parser\n    --features regex\nThen call HtmlScraper::new() and   build."
+        );
+        assert_eq!(fields.0["block"], "  a\n    b");
+
+        // Without the option the cleaner sees everything
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["block"], "a b");
+    }
+
+    #[test]
+    fn test_element_ext() {
+        let document = scraper::Html::parse_fragment(
+            r#"<div class="spec" data-unit="kg">
+                Weight: <b>1.2</b>
+                <script>track()</script>
+            </div>"#,
+        );
+        let selector = scraper::Selector::parse(".spec").unwrap();
+        let spec = document.select(&selector).next().unwrap();
+
+        assert_eq!(DefaultCleaner.clean(&spec.own_text()), "Weight:");
+        assert_eq!(spec.cleaned_text(&DefaultCleaner), "Weight: 1.2");
+        assert_eq!(spec.attr_or("data-unit", "g"), "kg");
+        assert_eq!(spec.attr_or("data-precision", "1"), "1");
+    }
+
+    #[test]
+    fn test_scrape_typed_table() {
+        let html = r#"
+        <table class="prices">
+            <thead><tr><th>Product</th><th>Price</th><th>Stock</th></tr></thead>
+            <tbody>
+                <tr><td>Desk lamp</td><td>19.99</td><td>12</td></tr>
+                <tr><td> Chair
+                    </td><td colspan="2">Sold out</td></tr>
+            </tbody>
+        </table>
+    "#;
+
+        let table: Table = HtmlScraper::default()
+            .scrape_typed_table(html, "table.prices")
+            .unwrap()
+            .unwrap();
+        assert_eq!(table.headers, ["Product", "Price", "Stock"]);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.get(0, "Price"), Some("19.99"));
+        assert_eq!(table.get(1, "Product"), Some("Chair"));
+        assert_eq!(table.get(1, "Stock"), Some("Sold out"));
+        assert_eq!(table.get(0, "Weight"), None);
+        assert_eq!(table.get(2, "Price"), None);
+
+        let scraper = HtmlScraper::default();
+        assert_eq!(scraper.scrape_typed_table(html, "table.missing").unwrap(), None);
+        assert!(matches!(
+            scraper.scrape_typed_table(html, "###"),
+            Err(ScrapeError::Config(ConfigError::InvalidSelector(_)))
+        ));
+    }
+
+    #[test]
+    fn test_list_marker_cleaner() {
+        let html = r#"
+        <ul>
+            <li>• Free shipping</li>
+            <li>  2. Second   </li>
+            <li>-5 °C rated</li>
+            <li>→ Arrow</li>
+        </ul>
+    "#;
+        let config = r#"{ "rules": [{ "type": "All", "selector": "li", "name": "items" }] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(ListMarkerCleaner::default())
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["items"], r#"["Free shipping","Second","-5 °C rated","→ Arrow"]"#);
+
+        let cleaner = ListMarkerCleaner::new(&["→"]);
+        assert_eq!(cleaner.clean("→ Arrow"), "Arrow");
+        assert_eq!(cleaner.clean("• Bullet"), "• Bullet");
+        assert_eq!(cleaner.clean("1. One"), "1. One");
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_sections_match_serial() {
+        let mut html = String::from("<html><body><table><tr><td>Row</td></tr></table>");
+        for i in 0..20 {
+            html.push_str(&format!(
+                r#"<section id="s{i}"><h2>Section {i}</h2><p>First {i}</p><p>Second {i}</p></section>"#
+            ));
+        }
+        html.push_str("</body></html>");
+        let config = r#"{
+            "rules": [
+                {
+                    "type": "All",
+                    "selector": "section",
+                    "name": "sections",
+                    "sub_rules": [
+                        { "type": "One", "selector": "h2", "name": "heading" },
+                        { "type": "All", "selector": "p", "name": "paragraphs" }
+                    ]
+                },
+                {
+                    "type": "All",
+                    "selector": "tr",
+                    "name": "rows",
+                    "sub_rules": [{ "type": "One", "selector": "td", "name": "cell" }]
+                },
+                { "type": "One", "selector": "h2", "name": "first" }
+            ]
+        }"#;
+
+        let serial: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(&html)
+            .unwrap();
+        let parallel: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .parallel_sections(true)
+            .build()
+            .scrape(&html)
+            .unwrap();
+        assert_eq!(parallel.0, serial.0);
+        assert_eq!(parallel.0["rows"], r#"["{\"cell\":\"Row\"}"]"#);
+
+        let sections: Vec<String> = serde_json::from_str(&parallel.0["sections"]).unwrap();
+        assert_eq!(sections.len(), 20);
+        assert_eq!(sections[3], r#"{"heading":"Section 3","paragraphs":"[\"First 3\",\"Second 3\"]"}"#);
+    }
+
+    #[test]
+    fn test_optional_rule() {
+        let html = r#"<h1>Title</h1><a href="/relative">Link</a>"#;
+        let config = r#"{
+            "rules": [
+                { "type": "One", "selector": "h1", "name": "title" },
+                { "type": "Optional", "rule": { "type": "One", "selector": ".missing", "name": "subtitle" } },
+                { "type": "Optional", "rule": {
+                    "type": "One", "selector": "a", "name": "link", "attribute": "href",
+                    "require_absolute_url": true
+                } }
+            ]
+        }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["title"], "Title");
+        assert!(!fields.0.contains_key("subtitle"));
+        assert!(!fields.0.contains_key("link"));
+
+        let parsed: ScraperConfig = serde_json::from_str(config).unwrap();
+        let reparsed: ScraperConfig = serde_json::from_str(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.to_string(), parsed.to_string());
+        assert!(parsed.to_string().contains(r#"{"type":"Optional","rule":{"type":"One","selector":".missing""#));
+    }
+
+    #[test]
+    fn test_scrape_value_structured() {
+        let html = r#"
+        <h1>[not, json]</h1>
+        <ul>
+            <li><a href="/a">A</a> <span class="tag">x</span><span class="tag">y</span></li>
+            <li><a href="/b">B</a></li>
+        </ul>
+        <dl><dt data-key="color" data-value="red">Color</dt></dl>
+        <img src="data:text/plain;base64,aGk=">
+    "#;
+        let config = r#"{ "rules": [
+            { "type": "Text", "selector": "h1", "name": "title" },
+            { "type": "All", "selector": "li", "name": "items", "sub_rules": [
+                { "type": "One", "selector": "a", "name": "link", "attribute": "href" },
+                { "type": "All", "selector": ".tag", "name": "tags" }
+            ] },
+            { "type": "All", "selector": "a", "name": "labels", "with_length": true },
+            { "type": "DynamicKey", "selector": "dt", "name": "specs",
+              "key_attribute": "data-key", "value_attribute": "data-value" },
+            { "type": "One", "selector": "img", "name": "image", "attribute": "src", "decode": "DataUri" },
+            { "type": "Coalesce", "name": "first_tags", "candidates": [
+                { "type": "One", "selector": ".missing", "name": "none" },
+                { "type": "All", "selector": ".tag", "name": "found" }
+            ] },
+            { "type": "All", "selector": ".missing", "name": "related", "default": "none", "sub_rules": [
+                { "type": "One", "selector": "a", "name": "link" }
+            ] }
+        ] }"#;
+
+        let value = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape_value::<Fields>(html)
+            .unwrap();
+        assert_eq!(value["title"], "[not, json]");
+        assert_eq!(
+            value["items"],
+            serde_json::json!([
+                { "link": "/a", "tags": ["x", "y"] },
+                { "link": "/b", "tags": [] }
+            ])
+        );
+        assert_eq!(value["labels"][1], serde_json::json!({ "value": "B", "length": 1 }));
+        assert_eq!(value["specs"], serde_json::json!({ "color": "red" }));
+        assert_eq!(value["image"]["mime"], "text/plain");
+        assert_eq!(value["first_tags"], serde_json::json!(["x", "y"]));
+        assert_eq!(value["related"], serde_json::json!(["none"]));
+    }
+
+    #[test]
+    fn test_invalid_selector_is_an_error() {
+        let html = "<div><a href=\"/a\">A</a></div>";
+        let configs = [
+            r####"{ "rules": [{ "type": "One", "selector": "###", "name": "title" }] }"####,
+            r####"{ "rules": [{ "type": "All", "selector": "div", "name": "items", "sub_rules": [
+                { "type": "Text", "selector": "###", "name": "text" }
+            ] }] }"####,
+            r####"{ "rules": [{ "type": "One", "selector": "div", "name": "link", "attribute": "href",
+                "attribute_from": "###" }] }"####,
+        ];
+
+        for config in configs {
+            let result = HtmlScraperBuilder::new()
+                .with_config(config)
+                .build()
+                .scrape::<Fields>(html);
+            assert!(matches!(result, Err(ScrapeError::Config(ConfigError::InvalidSelector(selector))) if selector == "###"));
+        }
+    }
+
+    #[test]
+    fn test_with_section() {
+        let html = r#"
+        <p>Preface</p>
+        <h1>Intro</h1>
+        <p>Welcome</p>
+        <div>
+            <h2>Setup <small>(5 min)</small></h2>
+            <p>Install</p>
+            <section><p>Configure</p></section>
+        </div>
+        <h2>Usage</h2>
+        <div><p>Run</p></div>
+    "#;
+        let config = r#"{ "rules": [
+            { "type": "All", "selector": "p", "name": "paragraphs", "with_section": true },
+            { "type": "All", "selector": "div", "name": "blocks", "with_section": true, "sub_rules": [
+                { "type": "One", "selector": "p", "name": "first" }
+            ] }
+        ] }"#;
+
+        let value = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape_value::<Fields>(html)
+            .unwrap();
+        assert_eq!(
+            value["paragraphs"],
+            serde_json::json!([
+                { "value": "Preface" },
+                { "value": "Welcome", "section": "Intro" },
+                { "value": "Install", "section": "Setup (5 min)" },
+                { "value": "Configure", "section": "Setup (5 min)" },
+                { "value": "Run", "section": "Usage" }
+            ])
+        );
+        // Headings inside a block come after its start
+        assert_eq!(
+            value["blocks"],
+            serde_json::json!([
+                { "first": "Install", "section": "Intro" },
+                { "first": "Run", "section": "Usage" }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_required_rules() {
+        let html = r#"<h1>Title</h1><ul><li>One</li></ul>"#;
+        let scrape = |config: &str| {
+            HtmlScraperBuilder::new()
+                .with_config(config)
+                .build()
+                .scrape::<Fields>(html)
+        };
+
+        let fields = scrape(r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title", "required": true },
+            { "type": "All", "selector": "li", "name": "items", "required": true },
+            { "type": "One", "selector": ".subtitle", "name": "subtitle" }
+        ] }"#)
+        .unwrap();
+        assert_eq!(fields.0["title"], "Title");
+        assert!(!fields.0.contains_key("subtitle"));
+
+        let result = scrape(r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title", "required": true },
+            { "type": "One", "selector": ".subtitle", "name": "subtitle", "required": true }
+        ] }"#);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "subtitle"));
+
+        // All and Text store a value even when nothing matches
+        let result = scrape(r#"{ "rules": [{ "type": "All", "selector": "tr", "name": "rows", "required": true }] }"#);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "rows"));
+        let result = scrape(r#"{ "rules": [{ "type": "Text", "selector": "p", "name": "body", "required": true }] }"#);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "body"));
+
+        let fields = scrape(r#"{ "rules": [
+            { "type": "Optional", "rule": { "type": "One", "selector": ".subtitle", "name": "subtitle", "required": true } }
+        ] }"#)
+        .unwrap();
+        assert!(fields.0.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_attributes_keep_first() {
+        let html = r#"<div class="a" class="b" data-id="1" data-id="2">Text</div>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "div", "name": "class", "attribute": "class" },
+            { "type": "One", "selector": "div", "name": "id", "attribute": "data-id" },
+            { "type": "All", "selector": ".b", "name": "second" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["class"], "a");
+        assert_eq!(fields.0["id"], "1");
+        assert_eq!(fields.0["second"], "[]");
+    }
+
+    #[test]
+    fn test_rule_defaults() {
+        let html = r#"<h1>Title</h1>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title", "default": "Untitled" },
+            { "type": "One", "selector": ".author", "name": "author", "default": "  Anonymous  " },
+            { "type": "One", "selector": "img", "name": "image", "attribute": "src", "default": "none" },
+            { "type": "Text", "selector": "p", "name": "body", "default": "No body" },
+            { "type": "All", "selector": "li", "name": "tags", "default": "untagged" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["title"], "Title");
+        assert_eq!(fields.0["author"], "Anonymous");
+        assert_eq!(fields.0["image"], "none");
+        assert_eq!(fields.0["body"], "No body");
+        assert_eq!(fields.0["tags"], r#"["untagged"]"#);
+    }
+
+    #[test]
+    fn test_heading_wildcard_selector() {
+        let html = r#"
+        <h1>One</h1><h2>Two</h2><h3>Three</h3><h4>Four</h4><h5>Five</h5><h6>Six</h6>
+        <header>Not a heading</header>
+        <article><h2 class="title">Article</h2><p><h3>Nested</h3></p></article>
+        <span data-kind="h*">Quoted</span>
+    "#;
+        let config = r#"{ "rules": [
+            { "type": "All", "selector": "h*", "name": "headings" },
+            { "type": "All", "selector": "article > h*.title, H*.missing", "name": "titles" },
+            { "type": "One", "selector": "[data-kind=\"h*\"]", "name": "quoted" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(
+            fields.0["headings"],
+            r#"["One","Two","Three","Four","Five","Six","Article","Nested"]"#
+        );
+        assert_eq!(fields.0["titles"], r#"["Article"]"#);
+        assert_eq!(fields.0["quoted"], "Quoted");
+
+        let config: ScraperConfig = serde_json::from_str(config).unwrap();
+        assert!(config.check().is_ok());
+    }
+
+    #[test]
+    fn test_chain_cleaner() {
+        struct Lowercase;
+        impl TextCleaner for Lowercase {
+            fn clean(&self, text: &str) -> String {
+                text.to_lowercase()
+            }
+        }
+        struct Exclaim;
+        impl TextCleaner for Exclaim {
+            fn clean(&self, text: &str) -> String {
+                format!("{}!", text)
+            }
+        }
+
+        let mut cleaner = ChainCleaner::new(vec![Arc::new(DefaultCleaner), Arc::new(Lowercase)]);
+        assert_eq!(cleaner.clean("  Hello\n   WORLD  "), "hello world");
+        cleaner.push(Arc::new(Exclaim));
+        assert_eq!(cleaner.clean("  Hello\n   WORLD  "), "hello world!");
+        assert_eq!(ChainCleaner::default().clean(" As is "), " As is ");
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(r#"{ "rules": [{ "type": "One", "selector": "h1", "name": "title" }] }"#)
+            .with_cleaner(cleaner)
+            .build()
+            .scrape("<h1>\n  Breaking\n  NEWS\n</h1>")
+            .unwrap();
+        assert_eq!(fields.0["title"], "breaking news!");
+    }
+
+    #[test]
+    fn test_extract_images() {
+        let html = r#"
+        <img src="/plain.png">
+        <img src="data:image/gif;base64,R0lGODlhAQABAAAAACw=" data-src="lazy.jpg">
+        <img data-lazy-src="https://cdn.example.com/lazy-2.jpg">
+        <img srcset="/small.jpg 480w, /large.jpg 1080w">
+        <img src="  " data-src="">
+        <img src="/plain.png" alt="Duplicate">
+    "#;
+        let scraper = HtmlScraper::default();
+
+        let images = scraper.extract_images(html, Some("https://example.com/gallery/")).unwrap();
+        assert_eq!(
+            images,
+            [
+                "https://example.com/plain.png",
+                "https://example.com/gallery/lazy.jpg",
+                "https://cdn.example.com/lazy-2.jpg",
+                "https://example.com/small.jpg",
+            ]
+        );
+
+        let images = scraper.extract_images(html, None).unwrap();
+        assert_eq!(images[1], "lazy.jpg");
+        assert!(matches!(
+            scraper.extract_images(html, Some("/relative")),
+            Err(ScrapeError::InvalidBaseUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_case_cleaners() {
+        assert_eq!(LowercaseCleaner.clean("Straße ÉTÉ"), "straße été");
+        assert_eq!(UppercaseCleaner.clean("Straße été"), "STRASSE ÉTÉ");
+
+        let cleaner = ChainCleaner::new(vec![Arc::new(DefaultCleaner), Arc::new(LowercaseCleaner)]);
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(r#"{ "rules": [{ "type": "All", "selector": "li", "name": "tags" }] }"#)
+            .with_cleaner(cleaner)
+            .build()
+            .scrape("<ul><li>\n  Rust </li><li>HTML\n  Parsing</li></ul>")
+            .unwrap();
+        assert_eq!(fields.0["tags"], r#"["rust","html parsing"]"#);
+    }
+
+    #[test]
+    fn test_output_name() {
+        let html = r#"<h1>Breaking</h1><ul><li><a href="/a">A</a></li></ul>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "headline_rule", "output_name": "title" },
+            { "type": "All", "selector": "li", "name": "item_rule", "output_name": "items", "sub_rules": [
+                { "type": "One", "selector": "a", "name": "link_rule", "output_name": "link", "attribute": "href" }
+            ] },
+            { "type": "Coalesce", "name": "first", "candidates": [
+                { "type": "One", "selector": "h1", "name": "candidate", "output_name": "renamed" }
+            ] }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["title"], "Breaking");
+        assert!(!fields.0.contains_key("headline_rule"));
+        assert_eq!(fields.0["first"], "Breaking");
+
+        let value = scraper.scrape_value::<Fields>(html).unwrap();
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["title", "items", "first"]);
+        assert_eq!(value["items"], serde_json::json!([{ "link": "/a" }]));
+
+        // Validators still refer to the rule by its name
+        #[derive(Debug, Deserialize)]
+        struct Headline;
+
+        impl ScrapeConfig for Headline {
+            fn get_config() -> ScraperConfig {
+                let config: ScraperConfig = serde_json::from_str(
+                    r#"{ "rules": [{ "type": "One", "selector": "h1", "name": "headline_rule", "output_name": "title" }] }"#,
+                )
+                .unwrap();
+                let lowercase = Validator::from_fn(|value| value.chars().all(|c| !c.is_uppercase()));
+                config.with_validators(HashMap::from([("headline_rule".to_string(), lowercase)]))
+            }
+        }
+
+        let result = HtmlScraper::default().scrape_value::<Headline>(html);
+        assert!(matches!(
+            result,
+            Err(ScrapeError::ValidationFailed { name, value }) if name == "headline_rule" && value == "Breaking"
+        ));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_replace_cleaner() {
+        use html_parser::RegexReplaceCleaner;
+
+        let html = r#"<p class="body">The sky is blue.[1] Grass is green.[23]</p><p class="date">Published: 2024-01-01</p>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": ".body", "name": "body" },
+            { "type": "One", "selector": ".date", "name": "date" }
+        ] }"#;
+        let cleaner = ChainCleaner::new(vec![
+            Arc::new(RegexReplaceCleaner::new(r"\[\d+\]", "").unwrap()),
+            Arc::new(RegexReplaceCleaner::new(r"^Published: (\S+)$", "$1").unwrap()),
+        ]);
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(cleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["body"], "The sky is blue. Grass is green.");
+        assert_eq!(fields.0["date"], "2024-01-01");
+
+        assert!(matches!(RegexReplaceCleaner::new("(unclosed", ""), Err(regex::Error::Syntax(_))));
+    }
+
+    #[test]
+    fn test_link_relations() {
+        let html = r#"
+        <head>
+            <link rel="canonical" href="https://example.com/article">
+            <link rel="alternate" hreflang="de" href="https://example.com/de/article">
+            <link rel="Alternate" type="application/rss+xml" href="/feed.xml">
+            <link rel="stylesheet preload" href="/style.css">
+            <link rel="icon" href="">
+        </head>
+    "#;
+
+        let relations = HtmlScraper::default().link_relations(html);
+        assert_eq!(relations["canonical"], ["https://example.com/article"]);
+        assert_eq!(relations["alternate"], ["https://example.com/de/article", "/feed.xml"]);
+        assert_eq!(relations["stylesheet"], ["/style.css"]);
+        assert_eq!(relations["preload"], ["/style.css"]);
+        assert!(!relations.contains_key("icon"));
+
+        let relations = HtmlScraperBuilder::new()
+            .with_base_url("https://example.com/")
+            .build()
+            .link_relations(html);
+        assert_eq!(relations["alternate"][1], "https://example.com/feed.xml");
+    }
+
+    #[test]
+    fn test_entity_decode_cleaner() {
+        let cleaner = EntityDecodeCleaner;
+        assert_eq!(
+            cleaner.clean("Tom &amp; Jerry&#8217;s &lt;show&gt;&nbsp;&#x2019;&#X41;&#160;"),
+            "Tom & Jerry’s <show>\u{a0}’A\u{a0}"
+        );
+        assert_eq!(cleaner.clean("&foo; &amp &#xZZ; &#0; & &;"), "&foo; &amp &#xZZ; &#0; & &;");
+        assert_eq!(cleaner.clean("&amp;amp;"), "&amp;");
+
+        // Double-encoded markup still holds entities after parsing
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(r#"{ "rules": [{ "type": "One", "selector": "p", "name": "text" }] }"#)
+            .with_cleaner(cleaner)
+            .build()
+            .scrape("<p>Rock &amp;amp; roll&amp;#8217;s</p>")
+            .unwrap();
+        assert_eq!(fields.0["text"], "Rock & roll’s");
+    }
+
+    #[test]
+    fn test_resolve_rule() {
+        let config = r#"{ "rules": [{
+            "type": "Resolve",
+            "name": "image",
+            "steps": [
+                { "selector": "meta[property='og:image']", "attribute": "content" },
+                { "selector": "img.hero", "attribute": "data-src" },
+                { "selector": ".caption" }
+            ],
+            "default": "  none  "
+        }] }"#;
+        let scraper = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build();
+
+        // The first step finds nothing, the second an element without the attribute
+        let html = r#"<img class="hero" src="/hero.png"><p class="caption"> Hero image </p>"#;
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["image"], "Hero image");
+        let sources = scraper.scrape_with_provenance::<Fields>(html).unwrap();
+        assert_eq!(sources["image"].selector, ".caption");
+        assert_eq!(sources["image"].source_tag, "p");
+
+        let fields: Fields = scraper.scrape(r#"<img class="hero" data-src="">"#).unwrap();
+        assert_eq!(fields.0["image"], "none");
+    }
+
+    #[test]
+    fn test_breadcrumbs() {
+        let html = r#"
+        <nav aria-label="breadcrumb">
+            <ol>
+                <li><a href="/">Home</a></li>
+                <li><a href="/books/"> Books </a></li>
+                <li aria-current="page">Rust in Action</li>
+            </ol>
+        </nav>
+        <div class="crumbs"><a href="/">Home</a> <span>›</span> <span>About</span></div>
+    "#;
+        let scraper = HtmlScraperBuilder::new().with_base_url("https://example.com/").build();
+
+        let crumbs = scraper.breadcrumbs(html, r#"nav[aria-label="breadcrumb"]"#).unwrap();
+        assert_eq!(
+            crumbs,
+            [
+                ("Home".to_string(), Some("https://example.com/".to_string())),
+                ("Books".to_string(), Some("https://example.com/books/".to_string())),
+                ("Rust in Action".to_string(), None),
+            ]
+        );
+
+        let crumbs = HtmlScraper::default().breadcrumbs(html, ".crumbs").unwrap();
+        assert_eq!(crumbs, [("Home".to_string(), Some("/".to_string())), ("About".to_string(), None)]);
+        assert!(HtmlScraper::default().breadcrumbs(html, ".missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_registered_cleaners() {
+        struct Noop;
+        impl TextCleaner for Noop {
+            fn clean(&self, text: &str) -> String {
+                text.to_string()
+            }
+        }
+
+        let html = "<p class=\"quote\" title=\"\n  Keep\n  these lines\n\">\n  Said\n  once\n</p>";
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": ".quote", "name": "title", "attribute": "title", "cleaner": "noop" },
+            { "type": "One", "selector": ".quote", "name": "cleaned_title", "attribute": "title" },
+            { "type": "One", "selector": ".quote", "name": "text" }
+        ] }"#;
+
+        let scraper = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .register_cleaner("noop", Noop)
+            .build();
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["title"], "\n  Keep\n  these lines\n");
+        assert_eq!(fields.0["cleaned_title"], "Keep these lines");
+        assert_eq!(fields.0["text"], "Said once");
+
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        assert!(matches!(
+            scraper.scrape::<Fields>(html),
+            Err(ScrapeError::UnknownCleaner(name)) if name == "noop"
+        ));
+    }
+
+    #[test]
+    fn test_count_rule() {
+        let html = r#"<ul class="results"><li>A</li><li>B</li><li>C</li></ul>"#;
+        let config = r#"{ "rules": [
+            { "type": "Count", "selector": ".results li", "name": "results" },
+            { "type": "Count", "selector": ".ads li", "name": "ads" }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["results"], "3");
+        assert_eq!(fields.0["ads"], "0");
+
+        let value = scraper.scrape_value::<Fields>(html).unwrap();
+        assert_eq!(value["results"], serde_json::json!(3));
+        assert_eq!(value["ads"], serde_json::json!(0));
+
+        let config = r#"{ "rules": [
+            { "type": "Count", "selector": ".ads li", "name": "ads", "required": true }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        assert!(matches!(
+            scraper.scrape::<Fields>(html),
+            Err(ScrapeError::RequiredFieldMissing(name)) if name == "ads"
+        ));
+    }
+
+    #[test]
+    fn test_meta_refresh() {
+        let scraper = HtmlScraper::default();
+        let html = r#"<head><meta HTTP-EQUIV="Refresh" content="5; URL='/next?page=2'"></head>"#;
+        assert_eq!(
+            scraper.meta_refresh(html, Some("https://example.com/list")),
+            Some((5, "https://example.com/next?page=2".to_string()))
+        );
+        assert_eq!(scraper.meta_refresh(html, None), Some((5, "/next?page=2".to_string())));
+
+        let html = r#"<meta http-equiv="refresh" content="0,https://example.org/">"#;
+        assert_eq!(
+            scraper.meta_refresh(html, Some("https://example.com/")),
+            Some((0, "https://example.org/".to_string()))
+        );
+
+        let scraper = HtmlScraperBuilder::new().with_base_url("https://example.com/a/").build();
+        let html = r#"<meta http-equiv="refresh" content="1.5;url = b">"#;
+        assert_eq!(scraper.meta_refresh(html, None), Some((1, "https://example.com/a/b".to_string())));
+
+        assert_eq!(scraper.meta_refresh(r#"<meta http-equiv="refresh" content="30">"#, None), None);
+        assert_eq!(scraper.meta_refresh(r#"<meta http-equiv="refresh" content="soon">"#, None), None);
+        assert_eq!(scraper.meta_refresh(r#"<meta name="refresh" content="5;url=/x">"#, None), None);
+    }
+
+    #[test]
+    fn test_exists_rule() {
+        let html = r#"<div class="product"><span class="banner sold-out">Sold out</span></div>"#;
+        let config = r#"{ "rules": [
+            { "type": "Exists", "selector": ".sold-out", "name": "sold_out" },
+            { "type": "Exists", "selector": ".discount", "name": "discounted" }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["sold_out"], "true");
+        assert_eq!(fields.0["discounted"], "false");
+
+        let value = scraper.scrape_value::<Fields>(html).unwrap();
+        assert_eq!(value["sold_out"], serde_json::Value::Bool(true));
+        assert_eq!(value["discounted"], serde_json::Value::Bool(false));
+    }
+
+    #[test]
+    fn test_list_tree_rule() {
+        let html = r##"
+        <ul class="toc">
+            <li><a href="#a">Intro</a>
+                <ol>
+                    <li>Background</li>
+                    <li>Goals</li>
+                </ol>
+            </li>
+            <li>Usage</li>
+            <ul><li>Stray nested</li></ul>
+        </ul>
+    "##;
+        let config = r#"{ "rules": [
+            { "type": "ListTree", "selector": ".toc", "name": "toc" },
+            { "type": "ListTree", "selector": ".missing", "name": "missing" }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build();
+
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(
+            fields.0["toc"],
+            r#"["Intro",["Background","Goals"],"Usage",["Stray nested"]]"#
+        );
+        assert!(!fields.0.contains_key("missing"));
+
+        let value = scraper.scrape_value::<Fields>(html).unwrap();
+        assert_eq!(
+            value["toc"],
+            serde_json::json!(["Intro", ["Background", "Goals"], "Usage", ["Stray nested"]])
+        );
+    }
+
+    #[test]
+    fn test_hidden_classes() {
+        let html = r#"
+        <h2 class="sr-only">Spring sale</h2>
+        <h2 class="title">Spring sale</h2>
+        <p class="lead"><span class="visually-hidden">Summary:</span> Everything half off</p>
+    "#;
+        let config = r#"{ "rules": [
+            { "type": "All", "selector": "h2", "name": "headings" },
+            { "type": "One", "selector": ".lead", "name": "lead" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .with_hidden_classes(vec!["sr-only".to_string(), "visually-hidden".to_string()])
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["headings"], r#"["Spring sale"]"#);
+        assert_eq!(fields.0["lead"], "Everything half off");
+
+        // Also for the methods that don't use rules, and inside hidden elements
+        let outline = HtmlScraperBuilder::new()
+            .with_hidden_classes(vec!["sr-only".to_string()])
+            .build()
+            .outline(r#"<div class="sr-only"><h2>Skip to content</h2></div><h1>Spring sale</h1>"#);
+        assert_eq!(outline, [(1, "Spring sale".to_string())]);
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["headings"], r#"["Spring sale","Spring sale"]"#);
+        assert_eq!(fields.0["lead"], "Summary: Everything half off");
+    }
+
+    #[test]
+    fn test_attributes_rule() {
+        let html = r#"<a class="docs" href="/guide" title="Read the guide">Guide</a>"#;
+        let config = r#"{ "rules": [
+            { "type": "Attributes", "selector": ".docs", "name": "link",
+              "attributes": ["href", "title", "rel"] },
+            { "type": "Attributes", "selector": ".docs", "name": "present",
+              "attributes": ["rel", "title", "href"], "skip_missing": true }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["link"], r#"{"href":"/guide","title":"Read the guide","rel":""}"#);
+        assert_eq!(fields.0["present"], r#"{"title":"Read the guide","href":"/guide"}"#);
+
+        let value = scraper.scrape_value::<Fields>(html).unwrap();
+        assert_eq!(
+            value["link"],
+            serde_json::json!({ "href": "/guide", "title": "Read the guide", "rel": "" })
+        );
+    }
+
+    #[test]
+    fn test_scrape_threaded() {
+        let html = r#"
+        <div class="comment" data-id="1">
+            <p class="body">First!</p>
+            <div class="replies">
+                <div class="comment" data-id="2">
+                    <p class="body">Welcome  aboard</p>
+                    <div class="comment" data-id="3"><p class="body">Thanks</p></div>
+                </div>
+                <div class="comment" data-id="4"><p class="body">Hi</p></div>
+            </div>
+        </div>
+        <div class="comment" data-id="5"><p class="body">Separate thread</p></div>
+    "#;
+        let records = HtmlScraper::default()
+            .scrape_threaded(html, ".comment", "data-id", ".body")
+            .unwrap();
+
+        let linkage: Vec<(&str, Option<&str>, usize)> = records
+            .iter()
+            .map(|r| (r.id.as_str(), r.parent_id.as_deref(), r.depth))
+            .collect();
+        assert_eq!(
+            linkage,
+            [
+                ("1", None, 0),
+                ("2", Some("1"), 1),
+                ("3", Some("2"), 2),
+                ("4", Some("1"), 1),
+                ("5", None, 0),
+            ]
+        );
+        let contents: Vec<&str> = records.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, ["First!", "Welcome aboard", "Thanks", "Hi", "Separate thread"]);
+    }
+
+    #[test]
+    fn test_extract_kind() {
+        let html = r#"<p class="intro">Read <em>this</em> first</p><a href="/a">A</a><a href="/b">B</a>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": ".intro", "name": "inner", "extract": "InnerHtml" },
+            { "type": "One", "selector": ".intro", "name": "outer", "extract": "OuterHtml" },
+            { "type": "One", "selector": ".intro", "name": "text" },
+            { "type": "All", "selector": "a", "name": "links", "extract": { "Attribute": "href" } },
+            { "type": "All", "selector": "a", "name": "anchors", "extract": "OuterHtml" }
+        ] }"#;
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["inner"], "Read <em>this</em> first");
+        assert_eq!(fields.0["outer"], r#"<p class="intro">Read <em>this</em> first</p>"#);
+        assert_eq!(fields.0["text"], "Read this first");
+        assert_eq!(fields.0["links"], r#"["/a","/b"]"#);
+        assert_eq!(fields.0["anchors"], r#"["<a href=\"/a\">A</a>","<a href=\"/b\">B</a>"]"#);
+
+        let mut rule = ScrapeRule::one(".intro", "inner");
+        if let ScrapeRule::One { extract, .. } = &mut rule {
+            *extract = ExtractKind::InnerHtml;
+        }
+        let json = serde_json::to_string(&rule).unwrap();
+        assert!(json.contains(r#""extract":"InnerHtml""#));
+    }
+
+    #[test]
+    fn test_scrape_value_rule_order() {
+        let html = r#"<h1>Title</h1><p>One</p><p>Two</p><span class="price">9</span>"#;
+        let config = r#"{ "rules": [
+            { "type": "Count", "selector": "p", "name": "paragraphs" },
+            { "type": "One", "selector": "h1", "name": "title", "output_name": "headline" },
+            { "type": "Optional", "rule": { "type": "One", "selector": ".price", "name": "price" } },
+            { "type": "Exists", "selector": "img", "name": "has_image" },
+            { "type": "All", "selector": "p", "name": "body" },
+            { "type": "Coalesce", "name": "author", "candidates": [
+                { "type": "One", "selector": ".author", "name": "byline" }
+            ] }
+        ] }"#;
+
+        let value = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape_value::<Fields>(html)
+            .unwrap();
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["paragraphs", "headline", "price", "has_image", "body"]);
+    }
+
+    #[test]
+    fn test_summary() {
+        let html = r#"
+        <article>
+            <p class="kicker"> </p>
+            <p>The council approved the new budget on Tuesday, after a long
+               debate about funding for parks and libraries.</p>
+            <p>Second paragraph.</p>
+        </article>
+    "#;
+        let scraper = HtmlScraper::default();
+        assert_eq!(
+            scraper.summary(html, "article", 8).unwrap().as_deref(),
+            Some("The council approved the new budget on Tuesday…")
+        );
+        assert_eq!(
+            scraper.summary(html, "article", 9).unwrap().as_deref(),
+            Some("The council approved the new budget on Tuesday, after…")
+        );
+        assert_eq!(
+            scraper.summary(html, "article", 100).unwrap().as_deref(),
+            Some("The council approved the new budget on Tuesday, after a long debate about funding for parks and libraries.")
+        );
+        assert_eq!(scraper.summary(html, ".missing", 8).unwrap(), None);
+        assert_eq!(scraper.summary("<article><div>No paragraphs</div></article>", "article", 8).unwrap(), None);
+    }
+
+    #[test]
+    fn test_element_filter() {
+        let html = r#"
+        <div class="result sponsored"><h3>Ad</h3></div>
+        <div class="result"><h3>First</h3></div>
+        <div class="result"><h3>Second</h3></div>
+    "#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": ".result h3", "name": "top" },
+            { "type": "All", "selector": ".result", "name": "results", "sub_rules": [
+                { "type": "One", "selector": "h3", "name": "title" }
+            ] },
+            { "type": "Count", "selector": ".result", "name": "count" },
+            { "type": "Text", "selector": "h3", "name": "titles", "separator": ", " }
+        ] }"#;
+        let not_sponsored = |element: &scraper::ElementRef| {
+            element
+                .ancestors()
+                .filter_map(scraper::ElementRef::wrap)
+                .chain(std::iter::once(*element))
+                .all(|el| !el.value().classes().any(|class| class == "sponsored"))
+        };
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_element_filter(Arc::new(not_sponsored))
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["top"], "First");
+        assert_eq!(fields.0["results"], r#"["{\"title\":\"First\"}","{\"title\":\"Second\"}"]"#);
+        assert_eq!(fields.0["count"], "2");
+        assert_eq!(fields.0["titles"], "First, Second");
+
+        let fields: Fields = HtmlScraperBuilder::new().with_config(config).build().scrape(html).unwrap();
+        assert_eq!(fields.0["top"], "Ad");
+        assert_eq!(fields.0["count"], "3");
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_scrape_many() {
+        let htmls: Vec<String> = (1..=3)
+            .map(|i| {
+                format!(
+                    r#"<h1 class="title">Story {i}</h1><div class="author">Author {i}</div>
+                    <div class="paragraph">Paragraph {i}</div>"#
+                )
+            })
+            .collect();
+
+        let articles = HtmlScraper::default().scrape_many::<NewsArticle>(&htmls);
+        assert_eq!(articles.len(), 3);
+        for (i, article) in articles.into_iter().enumerate() {
+            let article = article.unwrap();
+            assert_eq!(article.title, format!("Story {}", i + 1));
+            assert_eq!(article.author, format!("Author {}", i + 1));
+            assert_eq!(article.content, [format!("Paragraph {}", i + 1)]);
+        }
+
+        let broken = HtmlScraperBuilder::new().with_config("{ not json").build();
+        let results = broken.scrape_many::<NewsArticle>(&htmls);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| matches!(result, Err(ScrapeError::Config(ConfigError::UnsupportedFormat)))));
+    }
+
+    #[test]
+    fn test_scrape_document() {
+        let html = r#"<h1 class="title">Breaking News</h1><div class="author">John Doe</div>
+            <div class="paragraph">First.</div><p class="sr-only">Skip to content</p>"#;
+        let document = scraper::Html::parse_document(html);
+
+        let article: NewsArticle = HtmlScraper::default().scrape_document(&document).unwrap();
+        assert_eq!(article.title, "Breaking News");
+        assert_eq!(article.content, ["First."]);
+
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "headline" },
+            { "type": "All", "selector": "p", "name": "paragraphs" }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_hidden_classes(vec!["sr-only".to_string()])
+            .build();
+        let fields: Fields = scraper.scrape_document(&document).unwrap();
+        assert_eq!(fields.0["headline"], "Breaking News");
+        assert_eq!(fields.0["paragraphs"], "[]");
+
+        // The hidden paragraph was only left out of the scrape
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape_document(&document)
+            .unwrap();
+        assert_eq!(fields.0["paragraphs"], r#"["Skip to content"]"#);
+    }
+
+    #[test]
+    fn test_rule_builders() {
+        let built = ScraperConfig::builder()
+            .rule(ScrapeRule::one("h1", "title"))
+            .rule(ScrapeRule::one("a.canonical", "link").attribute("href"))
+            .rule(ScrapeRule::all("li", "items").sub_rules(vec![ScrapeRule::text("span", "label")]))
+            .build();
+        let parsed: ScraperConfig = serde_json::from_str(
+            r#"{ "rules": [
+                { "type": "One", "selector": "h1", "name": "title" },
+                { "type": "One", "selector": "a.canonical", "name": "link", "attribute": "href" },
+                { "type": "All", "selector": "li", "name": "items", "sub_rules": [
+                    { "type": "Text", "selector": "span", "name": "label" }
+                ] }
+            ] }"#,
+        )
+        .unwrap();
+        assert_eq!(serde_json::to_value(&built).unwrap(), serde_json::to_value(&parsed).unwrap());
+
+        // Rules without the field are left as they are
+        let rule = ScrapeRule::text("p", "body").attribute("title");
+        assert_eq!(serde_json::to_value(&rule).unwrap(), serde_json::to_value(ScrapeRule::text("p", "body")).unwrap());
+    }
+
+    #[test]
+    fn test_validate() {
+        let html = r#"<h1>Breaking News</h1><p>First.</p><p>Second.</p>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "headline" },
+            { "type": "All", "selector": "p", "name": "paragraphs" },
+            { "type": "One", "selector": ".byline", "name": "author", "required": true }
+        ] }"#;
+        let report = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .validate::<Fields>(html)
+            .unwrap();
+
+        assert_eq!(report.rules.len(), 3);
+        assert_eq!(report.rules[0].matches, 1);
+        assert_eq!(report.rules[1].matches, 2);
+        assert_eq!(
+            report.unmatched().collect::<Vec<_>>(),
+            [&RuleReport {
+                name: "author".to_string(),
+                compiled: true,
+                matches: 0,
+                missing_required: true,
+            }]
+        );
+        assert!(!report.is_ok());
+
+        let config = r#"{ "rules": [
+            { "type": "Coalesce", "name": "title", "candidates": [
+                { "type": "One", "selector": "h2", "name": "title" },
+                { "type": "One", "selector": "h1", "name": "title" }
+            ] },
+            { "type": "One", "selector": "p[", "name": "broken" }
+        ] }"#;
+        let report = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .validate::<Fields>(html)
+            .unwrap();
+        assert_eq!((report.rules[0].compiled, report.rules[0].matches), (true, 1));
+        assert_eq!((report.rules[1].compiled, report.rules[1].matches), (false, 0));
+        assert!(!report.rules[1].missing_required);
+    }
+
+    #[test]
+    fn test_all_matching_nothing() {
+        let html = r#"<h1>Breaking News</h1>"#;
+        let config = r#"{ "rules": [
+            { "type": "All", "selector": ".comment", "name": "comments" }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["comments"], "[]");
+        assert_eq!(scraper.scrape_value::<Fields>(html).unwrap()["comments"], serde_json::json!([]));
+
+        let config = r#"{ "rules": [
+            { "type": "All", "selector": ".comment", "name": "comments", "required": true }
+        ] }"#;
+        let result: Result<Fields, _> = HtmlScraperBuilder::new().with_config(config).build().scrape(html);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "comments"));
+    }
+
+    #[test]
+    fn test_invalid_selector_is_not_missing() {
+        let html = r#"<div></div>"#;
+        let scrape = |config: &str| -> Result<Fields, ScrapeError> {
+            HtmlScraperBuilder::new().with_config(config).build().scrape(html)
+        };
+
+        let result = scrape(r#"{ "rules": [{ "type": "One", "selector": ">>>", "name": "title", "required": true }] }"#);
+        assert!(matches!(result, Err(ScrapeError::Config(ConfigError::InvalidSelector(selector))) if selector == ">>>"));
+        let result = scrape(r#"{ "rules": [{ "type": "All", "selector": ">>>", "name": "items", "required": true }] }"#);
+        assert!(matches!(result, Err(ScrapeError::Config(ConfigError::InvalidSelector(selector))) if selector == ">>>"));
+
+        // Optional only tolerates a missing field, not a broken config
+        let result = scrape(
+            r#"{ "rules": [{ "type": "Optional", "rule": { "type": "One", "selector": ">>>", "name": "title", "required": true } }] }"#,
+        );
+        assert!(matches!(result, Err(ScrapeError::Config(ConfigError::InvalidSelector(_)))));
+
+        let result = scrape(r#"{ "rules": [{ "type": "One", "selector": "invalid", "name": "title", "required": true }] }"#);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "title"));
+    }
+
+    #[test]
+    fn test_text_separator() {
+        let scrape = |config: &str, html: &str| -> Fields {
+            HtmlScraperBuilder::new()
+                .with_config(config)
+                .with_cleaner(DefaultCleaner)
+                .build()
+                .scrape(html)
+                .unwrap()
+        };
+        let config = r#"{ "rules": [{ "type": "Text", "selector": "li", "name": "items", "separator": ", " }] }"#;
+
+        let fields = scrape(config, "<ul><li>a</li><li>\n    b</li><li>c</li></ul>");
+        assert_eq!(fields.0["items"], "a, b, c");
+
+        // Unlike with `paragraph_separator`, empty matches are kept
+        let html = "<ul><li>a</li><li></li><li>c</li></ul>";
+        assert_eq!(scrape(config, html).0["items"], "a, , c");
+        let config = r#"{ "rules": [{ "type": "Text", "selector": "li", "name": "items", "separator": ", ", "paragraph_separator": "; " }] }"#;
+        assert_eq!(scrape(config, html).0["items"], "a; c");
+
+        let config = r#"{ "rules": [{ "type": "Text", "selector": "li", "name": "items" }] }"#;
+        assert_eq!(scrape(config, html).0["items"], "a  c");
+    }
+
+    #[test]
+    fn test_coerce() {
+        let html = r#"<span class="count"> 42 </span><span class="price">4x</span>
+            <li>1.5</li><li>2</li><input type="checkbox" data-checked="TRUE">"#;
+        let config = ScraperConfig::builder()
+            .rule(ScrapeRule::one(".count", "count").coerce(Coerce::Int))
+            .rule(ScrapeRule::all("li", "ratings").coerce(Coerce::Float))
+            .rule(ScrapeRule::one("input", "checked").attribute("data-checked").coerce(Coerce::Bool))
+            .rule(ScrapeRule::text(".missing", "missing").coerce(Coerce::Int))
+            .build();
+        let scraper = HtmlScraperBuilder::new()
+            .with_config(&serde_json::to_string(&config).unwrap())
+            .build();
+
+        let value = scraper.scrape_value::<Fields>(html).unwrap();
+        assert_eq!(value["count"], serde_json::json!(42));
+        assert_eq!(value["ratings"], serde_json::json!([1.5, 2.0]));
+        assert_eq!(value["checked"], serde_json::json!(true));
+        assert_eq!(value["missing"], serde_json::json!(""));
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["count"], "42");
+
+        let config = r#"{ "rules": [{ "type": "One", "selector": ".price", "name": "price", "coerce": "Int" }] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        let result = scraper.scrape_value::<Fields>(html);
+        assert!(matches!(
+            result,
+            Err(ScrapeError::CoercionFailed { name, value }) if name == "price" && value == "4x"
+        ));
+        assert!(scraper.scrape::<Fields>(html).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_coerce_date() {
+        let html = r#"<time>01.03.2024</time><span>01.03.2024 12:30</span>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "time", "name": "date", "coerce": { "Date": "%d.%m.%Y" } },
+            { "type": "Text", "selector": "span", "name": "updated", "coerce": { "Date": "%d.%m.%Y %H:%M" } }
+        ] }"#;
+        let value = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape_value::<Fields>(html)
+            .unwrap();
+        assert_eq!(value["date"], "2024-03-01");
+        assert_eq!(value["updated"], "2024-03-01T12:30:00");
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let html = r#"<a class="home" href="/a/b">Home</a><a href="c">C</a><a href="mailto:x@example.com">Mail</a><a>None</a>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "a.home", "name": "home", "attribute": "href", "resolve_url": true },
+            { "type": "All", "selector": "a", "name": "links", "attribute": "href", "resolve_url": true }
+        ] }"#;
+        let scrape = |builder: HtmlScraperBuilder| builder.with_config(config).build().scrape::<Fields>(html);
+
+        let fields = scrape(HtmlScraperBuilder::new().with_base_url("https://example.com/x/")).unwrap();
+        assert_eq!(fields.0["home"], "https://example.com/a/b");
+        assert_eq!(
+            fields.0["links"],
+            r#"["https://example.com/a/b","https://example.com/x/c","mailto:x@example.com",""]"#
+        );
+
+        // Without a base URL values are left relative
+        let fields = scrape(HtmlScraperBuilder::new()).unwrap();
+        assert_eq!(fields.0["home"], "/a/b");
+
+        let result = scrape(HtmlScraperBuilder::new().with_base_url("example.com"));
+        assert!(matches!(result, Err(ScrapeError::InvalidBaseUrl(base)) if base == "example.com"));
+
+        // Also for rules requiring absolute URLs, rather than a `RelativeUrl`
+        let config = r#"{ "rules": [{ "type": "One", "selector": "a", "name": "home", "attribute": "href", "require_absolute_url": true }] }"#;
+        let builder = || HtmlScraperBuilder::new().with_config(config).with_base_url("example.com");
+        let result = builder().build().scrape::<Fields>(html);
+        assert!(matches!(result, Err(ScrapeError::InvalidBaseUrl(base)) if base == "example.com"));
+
+        // `try_build` reports it before any scrape
+        let result = builder().try_build::<Fields>();
+        assert!(matches!(result, Err(ConfigError::InvalidBaseUrl(base)) if base == "example.com"));
+        assert!(HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_base_url("https://example.com")
+            .try_build::<Fields>()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_resolve_url_references() {
+        let html = r#"
+            <a href="//cdn.example.com/lib.js">Protocol-relative</a>
+            <a href="../up">Parent</a>
+            <a href="../../../top?q=1#frag">Above the root</a>
+            <a href="localhost:8080/x">Host and port</a>
+            <a href="tel:911">Call</a>
+            <a href="mailto:123@example.com">Mail</a>
+        "#;
+        let config = r#"{ "rules": [
+            { "type": "All", "selector": "a", "name": "links", "attribute": "href", "resolve_url": true }
+        ] }"#;
+        let scraper = |base: &str| HtmlScraperBuilder::new().with_config(config).with_base_url(base).build();
+
+        let fields: Fields = scraper("https://example.com/a/b/").scrape(html).unwrap();
+        assert_eq!(
+            fields.0["links"],
+            r#"["https://cdn.example.com/lib.js","https://example.com/a/up","https://example.com/top?q=1#frag","https://localhost:8080/x","tel:911","mailto:123@example.com"]"#
+        );
+
+        // A host and port isn't an absolute URL, so it can't be a base
+        let result = scraper("localhost:8080").scrape::<Fields>(html);
+        assert!(matches!(result, Err(ScrapeError::InvalidBaseUrl(base)) if base == "localhost:8080"));
+
+        // Nor does it pass `require_absolute_url`
+        let absolute = |selector: &str| {
+            let config = format!(
+                r#"{{ "rules": [{{ "type": "One", "selector": "{selector}", "name": "link", "attribute": "href", "require_absolute_url": true }}] }}"#
+            );
+            HtmlScraperBuilder::new().with_config(&config).build().scrape::<Fields>(html)
+        };
+        let result = absolute("a[href^=localhost]");
+        assert!(matches!(result, Err(ScrapeError::RelativeUrl { value, .. }) if value == "localhost:8080/x"));
+
+        // Schemes followed by digits are still schemes
+        assert_eq!(absolute("a[href^=tel]").unwrap().0["link"], "tel:911");
+    }
+
+    #[test]
+    fn test_links_rule() {
+        let html = r#"
+        <div id="search">
+            <div class="g">
+                <a href="https://www.example.com/1"><h3 class="LC20lb">Title 1</h3></a>
+                <span class="st">Description 1</span>
+            </div>
+            <div class="g">
+                <a href=" https://www.example.com/2 "><h3 class="LC20lb">Title 2</h3></a>
+                <span class="st">Description 2</span>
+            </div>
+            <div class="g"><a name="more">More results</a></div>
+        </div>
+        "#;
+        let config = r#"{ "rules": [{ "type": "Links", "selector": ".g a", "name": "results" }] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Link {
+            text: String,
+            href: String,
+        }
+        #[derive(Deserialize)]
+        struct Results {
+            results: Vec<Link>,
+        }
+        impl ScrapeConfig for Results {
+            fn get_config() -> ScraperConfig {
+                ScraperConfig::new(vec![])
+            }
+        }
+
+        let results: Results = scraper.scrape_deserialize(html).unwrap();
+        assert_eq!(
+            results.results,
+            [
+                Link { text: "Title 1".to_string(), href: "https://www.example.com/1".to_string() },
+                Link { text: "Title 2".to_string(), href: "https://www.example.com/2".to_string() },
+            ]
+        );
+        assert_eq!(
+            scraper.scrape_value::<Fields>(html).unwrap()["results"],
+            serde_json::json!([
+                { "text": "Title 1", "href": "https://www.example.com/1" },
+                { "text": "Title 2", "href": "https://www.example.com/2" }
+            ])
+        );
+
+        let config = r#"{ "rules": [{ "type": "Links", "selector": ".g a[name]", "name": "results", "required": true }] }"#;
+        let result = HtmlScraperBuilder::new().with_config(config).build().scrape::<Fields>(html);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(_))));
+    }
+
+    #[test]
+    fn test_images_rule() {
+        let html = r#"
+        <div class="gallery">
+            <img src="/thumbs/1.jpg" alt="First product">
+            <img src="https://cdn.example.com/2.jpg">
+            <img data-src="/thumbs/3.jpg" alt="Lazy">
+        </div>
+        "#;
+        let config = r#"{ "rules": [{ "type": "Images", "selector": ".gallery img", "name": "images", "resolve_url": true }] }"#;
+
+        let value = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_base_url("https://example.com/products/")
+            .build()
+            .scrape_value::<Fields>(html)
+            .unwrap();
+        assert_eq!(
+            value["images"],
+            serde_json::json!([
+                { "src": "https://example.com/thumbs/1.jpg", "alt": "First product" },
+                { "src": "https://cdn.example.com/2.jpg", "alt": "" }
+            ])
+        );
+
+        let fields: Fields = HtmlScraperBuilder::new().with_config(config).build().scrape(html).unwrap();
+        assert_eq!(
+            fields.0["images"],
+            r#"[{"src":"/thumbs/1.jpg","alt":"First product"},{"src":"https://cdn.example.com/2.jpg","alt":""}]"#
+        );
+    }
+
+    #[test]
+    fn test_table_rule() {
+        let html = r#"
+        <table class="prices">
+            <thead><tr><th>Product</th><th>Price</th><th>Stock</th></tr></thead>
+            <tbody>
+                <tr><td>Apple</td><td>1.20</td><td>In stock</td></tr>
+                <tr><td>Pear</td><td>0.90</td></tr>
+            </tbody>
+        </table>
+        "#;
+        let config = r#"{ "rules": [
+            { "type": "Table", "selector": "table.prices", "name": "prices", "header": true },
+            { "type": "Table", "selector": "table.prices", "name": "cells" }
+        ] }"#;
+        let value = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape_value::<Fields>(html)
+            .unwrap();
+
+        assert_eq!(
+            value["prices"],
+            serde_json::json!([
+                { "Product": "Apple", "Price": "1.20", "Stock": "In stock" },
+                { "Product": "Pear", "Price": "0.90", "Stock": "" }
+            ])
+        );
+        assert_eq!(
+            value["cells"],
+            serde_json::json!([
+                ["Product", "Price", "Stock"],
+                ["Apple", "1.20", "In stock"],
+                ["Pear", "0.90", ""]
+            ])
+        );
+        let keys: Vec<&String> = value["prices"][0].as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["Product", "Price", "Stock"]);
+    }
+
+    #[test]
+    fn test_one_of_rule() {
+        let html = r#"<div class="headline"><h1 data-id="42">Breaking News</h1></div><h1>Other</h1>"#;
+        let config = r#"{ "rules": [
+            { "type": "OneOf", "selectors": ["h1.title", ".headline h1"], "name": "title" },
+            { "type": "OneOf", "selectors": ["h1.title", ".headline h1"], "name": "id", "attribute": "data-id" },
+            { "type": "OneOf", "selectors": ["h2", ".subtitle"], "name": "subtitle" }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        let fields: Fields = scraper.scrape(html).unwrap();
+        assert_eq!(fields.0["title"], "Breaking News");
+        assert_eq!(fields.0["id"], "42");
+        assert!(!fields.0.contains_key("subtitle"));
+
+        let report = scraper.validate::<Fields>(html).unwrap();
+        assert_eq!(report.rules[0].matches, 1);
+
+        let config = r#"{ "rules": [{ "type": "OneOf", "selectors": ["h2", ".subtitle"], "name": "subtitle", "required": true }] }"#;
+        let result = HtmlScraperBuilder::new().with_config(config).build().scrape::<Fields>(html);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "subtitle"));
+    }
+
+    #[test]
+    fn test_scrape_error() {
+        let html = r#"<h1>Breaking News</h1>"#;
+
+        // Loading the config fails with a `ConfigError` of its own
+        let broken = r#"{ "rules": [ "#;
+        assert!(matches!(Fields::from_config(broken), Err(ConfigError::UnsupportedFormat)));
+        let result: Result<Fields, _> = HtmlScraperBuilder::new().with_config(broken).build().scrape(html);
+        let err = result.unwrap_err();
+        assert!(matches!(&err, ScrapeError::Config(ConfigError::UnsupportedFormat)));
+        assert_eq!(err.to_string(), Fields::from_config(broken).unwrap_err().to_string());
+
+        // Scraping a loaded config fails with a `ScrapeError`
+        let config = r#"{ "rules": [{ "type": "One", "selector": ".byline", "name": "author", "required": true }] }"#;
+        let result = HtmlScraperBuilder::new().with_config(config).build().scrape_value::<Fields>(html);
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "author"));
+
+        assert!(matches!(
+            ScrapeError::from(ConfigError::InvalidSelector("p[".to_string())),
+            ScrapeError::Config(ConfigError::InvalidSelector(selector)) if selector == "p["
+        ));
+        assert!(matches!(
+            ScrapeError::from(ConfigError::UnsupportedFormat),
+            ScrapeError::Config(ConfigError::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn test_strict() {
+        let html = r#"<h1>Breaking News</h1><div class="author">  </div>
+            <article><p>First.</p></article>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "One", "selector": ".byline", "name": "byline", "default": "Staff" },
+            { "type": "One", "selector": ".author", "name": "author" },
+            { "type": "All", "selector": "article", "name": "articles", "sub_rules": [
+                { "type": "One", "selector": ".missing", "name": "missing" }
+            ] },
+            { "type": "Optional", "rule": { "type": "One", "selector": ".ad", "name": "ad" } },
+            { "type": "Optional", "rule": { "type": "Text", "selector": ".author", "name": "sub" } }
+        ] }"#;
+
+        // Lenient by default
+        let fields: Fields = HtmlScraperBuilder::new().with_config(config).build().scrape(html).unwrap();
+        assert_eq!(fields.0["byline"], "Staff");
+        assert!(fields.0["author"].trim().is_empty());
+
+        let result = HtmlScraperBuilder::new()
+            .with_config(config)
+            .strict(true)
+            .build()
+            .scrape::<Fields>(html);
+        let Err(ScrapeError::Unmatched(names)) = result else {
+            panic!("expected the unmatched rules");
+        };
+        assert_eq!(names, ["byline", "author"]);
+
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(r#"{ "rules": [{ "type": "One", "selector": "h1", "name": "title" }] }"#)
+            .strict(true)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["title"], "Breaking News");
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_strict_parallel_sections() {
+        let html = r#"<h1>Breaking News</h1><div class="author">  </div>
+            <article><p>First.</p></article><article><p>Second.</p></article>"#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "All", "selector": "article", "name": "articles", "sub_rules": [
+                { "type": "One", "selector": "p", "name": "text" }
+            ] },
+            { "type": "All", "selector": "section", "name": "sections", "sub_rules": [
+                { "type": "One", "selector": "p", "name": "text" }
+            ] },
+            { "type": "One", "selector": ".author", "name": "author" }
+        ] }"#;
+        let scraper = |strict: bool| {
+            HtmlScraperBuilder::new().with_config(config).parallel_sections(true).strict(strict).build()
+        };
+
+        let fields: Fields = scraper(false).scrape(html).unwrap();
+        let articles: Vec<String> = serde_json::from_str(&fields.0["articles"]).unwrap();
+        assert_eq!(articles, [r#"{"text":"First."}"#, r#"{"text":"Second."}"#]);
+
+        // Rules the sections are scraped in parallel for are checked too
+        let Err(ScrapeError::Unmatched(names)) = scraper(true).scrape::<Fields>(html) else {
+            panic!("expected the unmatched rules");
+        };
+        assert_eq!(names, ["sections", "author"]);
+    }
+
+    #[test]
+    fn test_contains_filter() {
+        let html = r#"
+        <div class="row">Brand: Acme</div>
+        <div class="row">PRICE: <span>19.99</span></div>
+        <div class="row">Stock: 4</div>
+        "#;
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": ".row", "name": "price", "contains": "Price" },
+            { "type": "All", "selector": ".row", "name": "details", "contains": ": a" },
+            { "type": "One", "selector": ".row", "name": "weight", "contains": "Weight" }
+        ] }"#;
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["price"], "PRICE: 19.99");
+        assert_eq!(fields.0["details"], r#"["Brand: Acme"]"#);
+        assert!(!fields.0.contains_key("weight"));
+    }
+
+    #[test]
+    fn test_where_attr_filter() {
+        let html = r#"
+        <a href="/about" data-type="internal">About</a>
+        <a href="https://example.org" data-type="external">Example</a>
+        <a href="https://example.net">Untyped</a>
+        <a href="https://example.com" data-type="external">Partner</a>
+        "#;
+        let config = r#"{ "rules": [
+            { "type": "All", "selector": "a", "name": "external", "attribute": "href",
+              "where_attr": ["data-type", "external"] },
+            { "type": "One", "selector": "a", "name": "partner", "where_attr": ["data-type", "external"],
+              "contains": "partner" }
+        ] }"#;
+        let fields: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape(html)
+            .unwrap();
+        assert_eq!(fields.0["external"], r#"["https://example.org","https://example.com"]"#);
+        assert_eq!(fields.0["partner"], "Partner");
+    }
+
+    #[test]
+    fn test_all_offset_and_limit() {
+        let html = r#"
+            <ul>
+                <li>1</li><li>2</li><li>3</li><li>4</li><li>5</li><li>6</li>
+            </ul>
+        "#;
+        let config = r#"{
+            "rules": [
+                {"type": "All", "selector": "li", "name": "page", "offset": 1, "limit": 3},
+                {"type": "All", "selector": "li", "name": "past_end", "offset": 6}
+            ]
+        }"#;
+
+        let scraper = HtmlScraperBuilder::new().with_config(config).build();
+        let result: Fields = scraper.scrape(html).unwrap();
+
+        assert_eq!(result.0["page"], r#"["2","3","4"]"#);
+        assert_eq!(result.0["past_end"], "[]");
+    }
+
+    #[test]
+    fn test_all_dedup() {
+        let html = r#"
+            <ul class="tags">
+                <li>rust</li><li> html </li><li>rust</li><li>scraping</li><li>html</li>
+            </ul>
+        "#;
+        let config = r#"{
+            "rules": [
+                {"type": "All", "selector": "li", "name": "tags", "dedup": true},
+                {"type": "All", "selector": "li", "name": "first_tags", "dedup": true, "limit": 3}
+            ]
+        }"#;
+
+        let scraper = HtmlScraperBuilder::new()
+            .with_config(config)
+            .with_cleaner(DefaultCleaner)
+            .build();
+        let result: Fields = scraper.scrape(html).unwrap();
+
+        assert_eq!(result.0["tags"], r#"["rust","html","scraping"]"#);
+        assert_eq!(result.0["first_tags"], r#"["rust","html"]"#);
+    }
+
+    #[cfg(feature = "toml_config")]
+    #[test]
+    fn test_detect_toml_config() {
+        let config = r#"
+            # Inline TOML is detected from the content
+            [[rules]]
+            type = "One"
+            selector = "h1"
+            name = "title"
+        "#;
+        let result: Fields = HtmlScraperBuilder::new()
+            .with_config(config)
+            .build()
+            .scrape("<h1>Breaking News</h1>")
+            .unwrap();
+        assert_eq!(result.0["title"], "Breaking News");
+
+        assert!(matches!(Fields::from_config("rules = nope"), Err(ConfigError::TomlParse(_))));
+        assert!(matches!(Fields::from_config("rules: nope"), Err(ConfigError::UnsupportedFormat)));
+        assert!(matches!(Fields::from_config(r#"{ "rules": [ "#), Err(ConfigError::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn test_try_build_precompiles_selectors() {
+        let config = r#"{ "rules": [
+            { "type": "One", "selector": "h1", "name": "title" },
+            { "type": "All", "selector": "li", "name": "items", "sub_rules": [{ "type": "One", "selector": "a", "name": "link" }] }
+        ] }"#;
+        let scraper = HtmlScraperBuilder::new().with_config(config).try_build::<Fields>().unwrap();
+        assert_eq!(scraper.compiled_selectors(), 3);
+
+        // Scraping, also with a clone, reuses the compiled selectors
+        let html = "<h1>News</h1><ul><li><a>One</a></li></ul>";
+        let _: Fields = scraper.scrape(html).unwrap();
+        let _: Fields = scraper.clone().scrape(html).unwrap();
+        assert_eq!(scraper.compiled_selectors(), 3);
+
+        // As do ad-hoc selectors
+        scraper.summary(html, "ul", 5).unwrap();
+        scraper.summary(html, "ul", 5).unwrap();
+        assert_eq!(scraper.compiled_selectors(), 5);
+        scraper.outline(html);
+        scraper.outline(html);
+        assert_eq!(scraper.compiled_selectors(), 6);
+
+        // Up to a limit, past which selectors are still compiled but not kept
+        for i in 0..2000 {
+            scraper.summary(html, &format!("#section-{i}"), 5).unwrap();
+        }
+        assert_eq!(scraper.compiled_selectors(), 1024);
+
+        let invalid = r#"{ "rules": [{ "type": "One", "selector": "p[", "name": "title" }] }"#;
+        let result = HtmlScraperBuilder::new().with_config(invalid).try_build::<Fields>();
+        assert!(matches!(result, Err(ConfigError::InvalidSelector(selector)) if selector == "p["));
+    }
+
+    #[test]
+    fn test_rule_options() {
+        let mut rule = ScrapeRule::one(".byline", "author");
+        rule.options_mut().required = true;
+        rule.options_mut().output_name = Some("by".to_string());
+
+        // The options sit next to the rule's own fields in configs
+        let json = serde_json::to_value(&rule).unwrap();
+        assert_eq!(json["required"], true);
+        assert_eq!(json["output_name"], "by");
+        let parsed: ScrapeRule = serde_json::from_value(json).unwrap();
+        assert!(parsed.required());
+        assert_eq!(parsed.output_name(), "by");
+
+        let config = ScraperConfig::new(vec![rule]).to_string();
+        let result = HtmlScraperBuilder::new().with_config(&config).build().scrape::<Fields>("<p>No byline</p>");
+        assert!(matches!(result, Err(ScrapeError::RequiredFieldMissing(name)) if name == "author"));
+    }
+
+    #[test]
+    fn test_validators_name_rules() {
+        fn config(validated: &str) -> ScraperConfig {
+            ScraperConfig::new(vec![
+                ScrapeRule::one("article", "article").sub_rules(vec![ScrapeRule::one("h1", "title")]),
+                ScrapeRule::all("li", "items").sub_rules(vec![ScrapeRule::one("a", "link")]),
+            ])
+            .with_validators(HashMap::from([(validated.to_string(), Validator::from_fn(|value| value == "News"))]))
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Title;
+        impl ScrapeConfig for Title {
+            fn get_config() -> ScraperConfig {
+                config("title")
+            }
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Link;
+        impl ScrapeConfig for Link {
+            fn get_config() -> ScraperConfig {
+                config("link")
+            }
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Typo;
+        impl ScrapeConfig for Typo {
+            fn get_config() -> ScraperConfig {
+                config("titel")
+            }
+        }
+
+        let scraper = HtmlScraper::default();
+        let html = "<article><h1>News</h1></article><ul><li><a>One</a></li></ul>";
+        assert!(scraper.scrape_value::<Title>(html).is_ok());
+        assert!(matches!(
+            scraper.scrape_value::<Title>("<article><h1>Sports</h1></article>"),
+            Err(ScrapeError::ValidationFailed { name, .. }) if name == "title"
+        ));
+
+        // The fields of an `All` rule's sub-rules are nested in its items,
+        // out of a validator's reach
+        assert!(matches!(scraper.scrape_value::<Link>(html), Err(ScrapeError::UnknownValidator(name)) if name == "link"));
+        assert!(matches!(scraper.scrape_value::<Typo>(html), Err(ScrapeError::UnknownValidator(name)) if name == "titel"));
+    }
 }